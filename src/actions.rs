@@ -1,11 +1,62 @@
-use crate::file_actions::FileActions;
-use crate::terminal::Stdio;
-use crate::{files, terminal};
+use crate::backup;
+use crate::diff;
+use crate::error::ParoError;
+use crate::file_actions::{FileActions, FileEntry, Method};
+use crate::files;
+use crate::hooks::{self, Hook};
+use crate::journal::{self, Change, Journal};
+use crate::manifest::Manifest;
+use crate::plan::{self, Action, Decision, Plan};
+use crate::settings::{ConflictPolicy, Operation};
+use crate::terminal::{self, Console, Inputs};
+use regex::RegexSet;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
 
 pub struct Actions {
   pub file_actions: FileActions,
-  pub stdio: Stdio,
+  pub console: Option<Box<dyn Console>>,
+  pub report: Report,
+  pub backup_dir: PathBuf,
+  pub(crate) journal: Journal,
+  answer_all: Option<Inputs>,
+}
+
+const CHOICES: [Inputs; 5] = [
+  Inputs::Yes,
+  Inputs::No,
+  Inputs::YesAll,
+  Inputs::NoAll,
+  Inputs::Exit,
+];
+const CONFLICT_CHOICES: [Inputs; 7] = [
+  Inputs::Yes,
+  Inputs::No,
+  Inputs::YesAll,
+  Inputs::NoAll,
+  Inputs::Diff,
+  Inputs::Backup,
+  Inputs::Exit,
+];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReportEntry {
+  pub destination: PathBuf,
+  pub action: Action,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+  pub entries: Vec<ReportEntry>,
+  pub log: Vec<String>,
+  pub errors: Vec<ParoError>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -29,17 +80,114 @@ impl fmt::Display for Log {
 
 impl Actions {
   pub fn new(file_actions: FileActions) -> Self {
+    Self {
+      console: Some(terminal::console()),
+      ..Self::headless(file_actions)
+    }
+  }
+
+  pub fn headless(mut file_actions: FileActions) -> Self {
+    // folders that could not be walked end up in the failure summary
+    let errors = std::mem::take(&mut file_actions.errors);
+    let state_dir = &file_actions.settings.state_dir;
+    let backup_dir = backup::new_backup_dir(state_dir, SystemTime::now());
+    // the journal of a run is named after its backup folder
+    let name = backup_dir.file_name().unwrap_or_default().to_string_lossy();
+    let journal = Journal::new(journal::journal_file(state_dir, &name));
     Self {
       file_actions,
-      stdio: Stdio::new(),
+      console: None,
+      report: Report {
+        errors,
+        ..Report::default()
+      },
+      backup_dir,
+      journal,
+      answer_all: None,
+    }
+  }
+
+  fn writeln(&mut self, message: String) {
+    match &mut self.console {
+      Some(console) => console.writeln(message),
+      None => self.report.log.push(message),
     }
   }
 
+  // without a console there is nobody to ask, conflicts are kept
+  fn dialog(
+    &mut self,
+    question: String,
+    conflict: Option<(&FileEntry, &Path)>,
+  ) -> Inputs {
+    match self.file_actions.settings.conflict_policy() {
+      ConflictPolicy::Overwrite | ConflictPolicy::Backup => return Inputs::Yes,
+      ConflictPolicy::Skip | ConflictPolicy::Fail => return Inputs::No,
+      ConflictPolicy::Prompt => {}
+    }
+    if let Some(input) = self.answer_all {
+      return input;
+    }
+
+    let choices: &[Inputs] = match conflict {
+      Some(_) => &CONFLICT_CHOICES,
+      None => &CHOICES,
+    };
+    loop {
+      let input = match &mut self.console {
+        Some(console) => console.dialog(question.clone(), choices),
+        None => Inputs::No,
+      };
+      match (input, conflict) {
+        (Inputs::YesAll, _) => {
+          self.answer_all = Some(Inputs::Yes);
+          return Inputs::Yes;
+        }
+        (Inputs::NoAll, _) => {
+          self.answer_all = Some(Inputs::No);
+          return Inputs::No;
+        }
+        (Inputs::Diff, Some((value, key))) => self.diff(value, key),
+        _ => return input,
+      }
+    }
+  }
+
+  fn diff(&mut self, value: &FileEntry, key: &Path) {
+    let lines = match (fs::read_to_string(key), fs::read_to_string(&value.path))
+    {
+      (Ok(current), Ok(dotfile)) => diff::unified(
+        &current,
+        &dotfile,
+        &key.to_string_lossy(),
+        &value.path.to_string_lossy(),
+      ),
+      _ => vec![format!("Files {:?} and {:?} differ", key, value.path)],
+    };
+    for line in lines {
+      self.writeln(line);
+    }
+  }
+
+  fn conflict(&mut self, key: PathBuf) {
+    self.record(&key, Action::Conflict);
+    if self.file_actions.settings.conflict != ConflictPolicy::Skip {
+      self.report.errors.push(ParoError::Conflict(key));
+    }
+  }
+
+  fn record(&mut self, key: &Path, action: Action) {
+    self.report.entries.push(ReportEntry {
+      destination: key.to_path_buf(),
+      action,
+    });
+  }
+
   fn log(&mut self, level: Log, message: String) {
     if (level as u8 <= self.file_actions.settings.verbose)
       || self.file_actions.settings.dry_run
     {
-      self.stdio.writeln(message);
+      self.writeln(message);
     }
   }
 
@@ -56,116 +204,666 @@ impl Actions {
     self.log(Log::Warning, message);
   }
 
-  fn run<F: FnOnce()>(&mut self, callback: F) {
-    if !self.file_actions.settings.dry_run {
-      callback();
+  fn fail(&mut self, error: ParoError) {
+    self.warn(format!("failed {}", error));
+    self.report.errors.push(error);
+  }
+
+  // every change applied is recorded in the journal for paro undo
+  fn run(&mut self, changes: Vec<Change>) -> bool {
+    if self.file_actions.settings.dry_run {
+      return true;
+    }
+
+    for change in changes {
+      if let Err(err) = change.apply() {
+        self.fail(err);
+        return false;
+      }
+      if let Err(err) = self.journal.record(&change) {
+        self.fail(err);
+      }
+    }
+    true
+  }
+
+  // files that differ from the dotfile are moved into the backup folder
+  fn backup_target(
+    &mut self,
+    value: &FileEntry,
+    key: &Path,
+    always: bool,
+  ) -> Option<PathBuf> {
+    let settings = &self.file_actions.settings;
+    let copy = value.method == Method::Copy;
+    // a real folder may hold files paro never managed, it is never deleted
+    let real_dir = key.is_dir() && !key.is_symlink();
+    if files::is_same_file(&value.path, key, copy).unwrap_or(false)
+      || (settings.no_backup
+        && !always
+        && !real_dir
+        && settings.conflict_policy() != ConflictPolicy::Backup)
+    {
+      return None;
+    }
+
+    let target =
+      backup::backup_path(&self.backup_dir, &settings.destination, key);
+    self.info(format!("backup {:?} -> {:?}", key, target));
+    Some(target)
+  }
+
+  fn decide(&mut self, value: &FileEntry, key: &Path) -> Option<Decision> {
+    let settings = &self.file_actions.settings;
+    let policy = settings.conflict_policy();
+    let decision = if settings.operation == Operation::Down {
+      plan::decide_down(value, key, policy)
+    } else {
+      plan::decide_up(value, key, policy)
+    };
+    match decision {
+      Ok(decision) => Some(decision),
+      Err(err) => {
+        self.fail(ParoError::io(err, key));
+        None
+      }
+    }
+  }
+
+  fn install(&mut self, value: &FileEntry, key: &Path) {
+    match value.method {
+      Method::Copy => {
+        self.record(key, Action::Copy);
+        self.info(format!("copying {:?} -> {:?}", value.path, key));
+        self.run(vec![Change::Copy {
+          path: key.to_path_buf(),
+          origin: value.path.clone(),
+        }]);
+      }
+      Method::Link => {
+        self.record(key, Action::Link);
+        self.info(format!("linking {:?} -> {:?}", value.path, key));
+        self.run(vec![Change::Link {
+          path: key.to_path_buf(),
+          origin: value.path.clone(),
+        }]);
+      }
     }
   }
 
+  fn replace(&mut self, value: &FileEntry, key: &Path) {
+    let backup = self.backup_target(value, key, false);
+    let path = key.to_path_buf();
+    let origin = value.path.clone();
+    self.run(vec![
+      Change::remove(key, backup),
+      match value.method {
+        Method::Copy => Change::Copy { path, origin },
+        Method::Link => Change::Link { path, origin },
+      },
+    ]);
+  }
+
+  fn remove(&mut self, value: &FileEntry, key: &Path, always: bool) -> bool {
+    let backup = self.backup_target(value, key, always);
+    self.run(vec![Change::remove(key, backup)])
+  }
+
   pub fn up(&mut self) {
+    self.answer_all = None;
     for (key, value) in self.file_actions.actions.clone() {
-      if files::is_same_file(&value.path, &key).unwrap() {
-        self.debug(format!("keeping current {:?}", key));
-        continue;
-      }
+      let decision = match self.decide(&value, &key) {
+        Some(decision) => decision,
+        None => continue,
+      };
 
-      if value.path.is_dir() {
-        if !key.exists() {
+      match decision.action {
+        Action::Keep => {
+          self.debug(format!("keeping current {:?}", key));
+        }
+        Action::Mkdir => {
+          self.record(&key, Action::Mkdir);
           self.info(format!("mkdir {:?}", key));
-          self.run(|| files::create_dir(&key));
+          self.run(vec![Change::Mkdir { path: key.clone() }]);
         }
-        continue;
+        Action::Overwrite => {
+          self.record(&key, Action::Overwrite);
+          self.warn(format!(
+            "overwrite {} {:?} -> {:?}",
+            value.method, value.path, key
+          ));
+          self.replace(&value, &key);
+        }
+        Action::Backup => {
+          self.record(&key, Action::Backup);
+          self.warn(format!(
+            "backup and {} {:?} -> {:?}",
+            value.method, value.path, key
+          ));
+          self.replace(&value, &key);
+        }
+        Action::Skip => {
+          self.record(&key, Action::Skip);
+          self.info(format!("skipping conflict {:?}", key));
+        }
+        Action::Conflict => {
+          match self.dialog(
+            format!("Overwrite? {}", &key.to_string_lossy()),
+            Some((&value, &key)),
+          ) {
+            Inputs::Exit => {
+              self.trace("Exiting".to_string());
+              break;
+            }
+            input @ (Inputs::Yes | Inputs::Backup) => {
+              self.warn(format!("replacing existing {:?}", key));
+              if self.remove(&value, &key, input == Inputs::Backup) {
+                self.install(&value, &key);
+              }
+            }
+            _ => {
+              self.debug(format!("keeping current {:?}", key));
+              self.conflict(key);
+            }
+          }
+        }
+        _ => self.install(&value, &key),
       }
+    }
+  }
 
-      if self.file_actions.settings.force {
-        self.warn(format!("overwrite {:?} -> {:?}", value.path, key));
-        self.run(|| files::overwrite_symlink(&value.path, &key));
-        continue;
-      }
+  pub fn down(&mut self) {
+    self.answer_all = None;
+    let mut removed = BTreeSet::new();
+    for (key, value) in self.file_actions.actions.clone() {
+      let decision = match self.decide(&value, &key) {
+        Some(decision) => decision,
+        None => continue,
+      };
 
-      if key.exists() {
-        match self
-          .stdio
-          .dialog(format!("Overwrite? {}", &key.to_string_lossy()))
-        {
-          terminal::Inputs::Exit => {
-            self.trace("Exiting".to_string());
-            break;
+      match decision.action {
+        Action::Delete => {
+          self.record(&key, Action::Delete);
+          self.warn(format!(
+            "deleting {} {:?} ({})",
+            value.method, key, decision.reason
+          ));
+          if self.remove(&value, &key, false) {
+            removed.insert(key);
           }
-          terminal::Inputs::No => {
-            self.debug(format!("keeping current {:?}", key));
-            continue;
+        }
+        Action::Backup => {
+          self.record(&key, Action::Backup);
+          self.warn(format!("backup and delete {:?}", key));
+          if self.remove(&value, &key, false) {
+            removed.insert(key);
           }
-          terminal::Inputs::Yes => {
-            self.warn(format!("deleting existing {:?}", key));
-            self.run(|| files::delete_file(&key));
+        }
+        Action::Skip => {
+          self.record(&key, Action::Skip);
+          self.info(format!("skipping conflict {:?}", key));
+        }
+        Action::Conflict => {
+          match self.dialog(
+            format!(
+              "File {} is different from the managed by paro, delete anyway?",
+              &key.to_string_lossy()
+            ),
+            Some((&value, &key)),
+          ) {
+            Inputs::Exit => {
+              self.trace("Exiting".to_string());
+              break;
+            }
+            input @ (Inputs::Yes | Inputs::Backup) => {
+              self.record(&key, Action::Delete);
+              self.warn(format!("deleting existing {:?}", key));
+              if self.remove(&value, &key, input == Inputs::Backup) {
+                removed.insert(key);
+              }
+            }
+            _ => {
+              self.debug(format!("not deleting existing {:?}", key));
+              self.conflict(key);
+            }
           }
         }
+        _ => {
+          self
+            .debug(format!("keeping current {:?} ({})", key, decision.reason));
+        }
       }
-
-      self.info(format!("linking {:?} -> {:?}", value.path, key));
-      self.run(|| files::create_symlink(&value.path, &key));
     }
+    self.remove_empty_dirs(removed);
   }
 
-  pub fn down(&mut self) {
-    for (key, value) in self.file_actions.actions.clone() {
-      if files::is_same_file(&value.path, &key).unwrap() {
-        self.warn(format!("deleting current {:?}", key));
-        self.run(|| files::delete_file(&key));
+  // folders paro created or emptied, deepest first, never the destination
+  fn remove_empty_dirs(&mut self, mut removed: BTreeSet<PathBuf>) {
+    let destination = PathBuf::from(&self.file_actions.settings.destination);
+    let mut dirs: BTreeSet<PathBuf> = self
+      .file_actions
+      .actions
+      .iter()
+      .filter(|(_, value)| value.file_type.is_dir() && !value.symlink_dir)
+      .map(|(key, _)| key.clone())
+      .collect();
+    for path in &removed {
+      dirs.extend(path.ancestors().skip(1).map(Path::to_path_buf));
+    }
+    let mut dirs: Vec<PathBuf> = dirs
+      .into_iter()
+      .filter(|dir| dir.starts_with(&destination) && *dir != destination)
+      .collect();
+    dirs.sort_by_key(|dir| Reverse(dir.components().count()));
+
+    for dir in dirs {
+      // files removed by this run are still there with --dry-run
+      let is_empty = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+          .filter_map(|entry| entry.ok())
+          .all(|entry| removed.contains(&entry.path())),
+        Err(_) => false,
+      };
+      if !is_empty || dir.is_symlink() {
+        self.debug(format!("keeping folder {:?}", dir));
         continue;
       }
 
-      if value.path.is_dir() {
-        self.debug(format!("not deleting folders {:?}", key));
-        continue;
+      self.record(&dir, Action::Delete);
+      self.info(format!("removing empty folder {:?}", dir));
+      if self.run(vec![Change::Rmdir { path: dir.clone() }]) {
+        removed.insert(dir);
       }
+    }
+  }
 
+  pub fn sync(&mut self) -> Result<(), ParoError> {
+    self.answer_all = None;
+    for key in self.file_actions.stale_links()? {
       if self.file_actions.settings.force {
-        self.warn(format!("force deleting {:?} -> {:?}", value.path, key));
-        self.run(|| files::force_delete_file(&key));
+        self.record(&key, Action::Delete);
+        self.warn(format!("deleting stale {:?}", key));
+        self.run(vec![Change::remove(&key, None)]);
         continue;
       }
 
-      if key.exists() {
-        match self.stdio.dialog(format!(
-          "File {} is different from the managed by paro, delete anyway?",
+      match self.dialog(
+        format!(
+          "Link {} is no longer managed by paro, delete it?",
           &key.to_string_lossy()
-        )) {
-          terminal::Inputs::Exit => {
-            self.trace("Exiting".to_string());
-            break;
-          }
-          terminal::Inputs::Yes => {
-            self.warn(format!("deleting existing {:?}", key));
-            self.run(|| files::delete_file(&key));
-            continue;
-          }
-          terminal::Inputs::No => {
-            self.debug(format!("not deleting existing {:?}", key));
+        ),
+        None,
+      ) {
+        Inputs::Exit => {
+          self.trace("Exiting".to_string());
+          break;
+        }
+        Inputs::Yes => {
+          self.record(&key, Action::Delete);
+          self.warn(format!("deleting stale {:?}", key));
+          self.run(vec![Change::remove(&key, None)]);
+        }
+        _ => {
+          self.debug(format!("keeping stale {:?}", key));
+        }
+      }
+    }
+    Ok(())
+  }
+
+  pub fn hooks(&mut self, hook: Hook) -> Result<(), ParoError> {
+    if self.file_actions.settings.skip_hooks {
+      self.debug(format!("skipping {} hooks", hook));
+      return Ok(());
+    }
+
+    let env = hooks::hook_env(&self.file_actions.settings, hook);
+    for dir in self.file_actions.settings.directories.clone() {
+      for file in hooks::hook_files(&dir, hook) {
+        self.info(format!("running {} hook {:?}", hook, file));
+        let output = hooks::run_hook(&file, &dir, &env)
+          .map_err(|err| ParoError::io(err, &file))?;
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+          self.info(line.to_string());
+        }
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+          self.warn(line.to_string());
+        }
+
+        if !output.status.success() {
+          return Err(ParoError::Hook(format!(
+            "{} hook {:?} failed with {}",
+            hook, file, output.status
+          )));
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn add_target(&self) -> Result<PathBuf, ParoError> {
+    let settings = &self.file_actions.settings;
+    let mut target = match settings.directories.first() {
+      Some(dir) => PathBuf::from(dir),
+      None => {
+        return Err(ParoError::Config(
+          "paro add needs a dotfiles directory".to_string(),
+        ))
+      }
+    };
+    match (settings.tags.as_slice(), settings.host) {
+      ([], false) => {}
+      ([tag], false) => target.push(format!("tag-{}", tag)),
+      ([], true) => target.push(format!("host-{}", settings.hostname)),
+      _ => {
+        return Err(ParoError::Config(
+          "paro add takes a single tag or --host".to_string(),
+        ))
+      }
+    }
+    Ok(target)
+  }
+
+  fn add_path(&mut self, path: &Path, target: &Path) -> Result<(), ParoError> {
+    let destination = Path::new(&self.file_actions.settings.destination);
+    let parent = match path.parent() {
+      Some(parent) if !parent.as_os_str().is_empty() => parent,
+      _ => Path::new("."),
+    };
+    let path = fs::canonicalize(parent)
+      .map_err(|err| ParoError::io(err, path))?
+      .join(path.file_name().unwrap_or_default());
+
+    if !files::is_present(&path) {
+      return Err(ParoError::io(io::ErrorKind::NotFound.into(), &path));
+    }
+    if path.is_symlink() {
+      return Err(ParoError::Config(format!(
+        "{:?} is already a symlink",
+        path
+      )));
+    }
+    let dotfile = files::revert_root_dir(&path, destination, target)
+      .ok_or_else(|| {
+        ParoError::Config(format!(
+          "{:?} is not inside the destination folder {:?}",
+          path, destination
+        ))
+      })?;
+    if files::is_present(&dotfile) {
+      return Err(ParoError::Config(format!("{:?} already exists", dotfile)));
+    }
+    self.check_hidden(&path, target)?;
+
+    self.info(format!("moving {:?} -> {:?}", path, dotfile));
+    self.info(format!("linking {:?} -> {:?}", dotfile, path));
+    self.run(vec![
+      Change::Move {
+        path: dotfile.clone(),
+        origin: path.clone(),
+      },
+      Change::Link {
+        path,
+        origin: dotfile,
+      },
+    ]);
+    Ok(())
+  }
+
+  // up hides the top level names not in no-hide, an added file has to come
+  // back to the same place
+  fn check_hidden(&self, path: &Path, target: &Path) -> Result<(), ParoError> {
+    let settings = &self.file_actions.settings;
+    let destination = Path::new(&settings.destination);
+    let relative = path.strip_prefix(destination).ok();
+    let top = match relative.and_then(|relative| relative.iter().next()) {
+      Some(top) => destination.join(top),
+      None => return Ok(()),
+    };
+    let dotfile =
+      files::revert_root_dir(&top, destination, target).unwrap_or_default();
+    let no_hide = RegexSet::new(&settings.no_hide)?;
+    let hidden = files::is_hidden(top.file_name().unwrap_or_default());
+    match (hidden, no_hide.is_match(&dotfile.to_string_lossy())) {
+      (false, false) => Err(ParoError::Config(format!(
+        "{:?} is not hidden, add {:?} to no-hide to manage it",
+        top, dotfile
+      ))),
+      (true, true) => Err(ParoError::Config(format!(
+        "{:?} would be installed without its dot, {:?} is in no-hide",
+        top, dotfile
+      ))),
+      _ => Ok(()),
+    }
+  }
+
+  pub fn add(&mut self) -> Result<(), ParoError> {
+    let target = self.add_target()?;
+    for path in self.file_actions.settings.add.clone() {
+      if let Err(err) = self.add_path(Path::new(&path), &target) {
+        self.fail(err);
+      }
+    }
+    self.summary()
+  }
+
+  fn restore_file(&mut self, backup: &Path, key: &Path) {
+    let settings = &self.file_actions.settings;
+    // a file under a symlinked folder would be written into the dotfiles
+    let destination = Path::new(&settings.destination);
+    if key
+      .ancestors()
+      .skip(1)
+      .take_while(|dir| *dir != destination)
+      .any(|dir| dir.is_symlink())
+    {
+      self.debug(format!("not restoring into a symlinked folder {:?}", key));
+      self.conflict(key.to_path_buf());
+      return;
+    }
+
+    let replaces_file = files::is_present(key) && !key.is_symlink();
+    if replaces_file && !settings.force {
+      self.debug(format!("not replacing existing {:?}", key));
+      self.conflict(key.to_path_buf());
+      return;
+    }
+
+    // the current file is backed up as any other replaced file
+    let current = (replaces_file && !settings.no_backup).then(|| {
+      backup::backup_path(&self.backup_dir, &settings.destination, key)
+    });
+    self.record(key, Action::Restore);
+    self.info(format!("restoring {:?} -> {:?}", backup, key));
+    let mut changes = Vec::new();
+    if files::is_present(key) {
+      changes.push(Change::remove(key, current));
+    }
+    changes.push(Change::Move {
+      path: key.to_path_buf(),
+      origin: backup.to_path_buf(),
+    });
+    self.run(changes);
+  }
+
+  // a folder replaced by a symlinked folder goes back in place of the link
+  fn restore_dir(&mut self, backup: &Path, key: &Path) {
+    self.record(key, Action::Restore);
+    self.info(format!("restoring {:?} -> {:?}", backup, key));
+    self.run(vec![
+      Change::remove(key, None),
+      Change::Move {
+        path: key.to_path_buf(),
+        origin: backup.to_path_buf(),
+      },
+    ]);
+  }
+
+  pub fn restore(&mut self) -> Result<(), ParoError> {
+    let settings = &self.file_actions.settings;
+    let backups = backup::backups_dir(&settings.state_dir);
+    let backup_dir = if settings.restore.is_empty() {
+      backup::latest_backup_dir(&settings.state_dir)
+    } else {
+      Some(backups.join(&settings.restore))
+    }
+    .filter(|dir| dir.is_dir())
+    .ok_or_else(|| {
+      ParoError::Config(format!("No backup found in {:?}", backups))
+    })?;
+    let destination = PathBuf::from(&settings.destination);
+
+    self.trace(format!("Restore {:?}", backup_dir));
+    let mut entries = WalkDir::new(&backup_dir)
+      .min_depth(1)
+      .sort_by_file_name()
+      .into_iter();
+    while let Some(entry) = entries.next() {
+      let entry =
+        entry.map_err(|err| ParoError::io(err.into(), &backup_dir))?;
+      let key = match entry.path().strip_prefix(&backup_dir) {
+        Ok(relative) => destination.join(relative),
+        Err(_) => continue,
+      };
+      if !entry.file_type().is_dir() {
+        self.restore_file(entry.path(), &key);
+      } else if key.is_symlink() {
+        entries.skip_current_dir();
+        self.restore_dir(entry.path(), &key);
+      }
+    }
+
+    // folders left empty by the restored files are not needed anymore
+    if !self.file_actions.settings.dry_run {
+      for entry in WalkDir::new(&backup_dir).contents_first(true) {
+        match entry {
+          Ok(entry) if entry.file_type().is_dir() => {
+            let _ = fs::remove_dir(entry.path());
           }
+          _ => {}
+        }
+      }
+    }
+    self.summary()
+  }
+
+  pub fn undo(&mut self) -> Result<(), ParoError> {
+    let state_dir = &self.file_actions.settings.state_dir;
+    let path = journal::latest_journal(state_dir).ok_or_else(|| {
+      ParoError::Config(format!(
+        "No run to undo in {:?}",
+        journal::journal_dir(state_dir)
+      ))
+    })?;
+
+    self.trace(format!("Undo {:?}", path));
+    let mut failed = Vec::new();
+    for change in journal::read_journal(&path)?.into_iter().rev() {
+      self.info(format!("undo {}", change));
+      if !self.file_actions.settings.dry_run {
+        if let Err(err) = change.undo() {
+          self.fail(err);
+          failed.push(change);
         }
       }
+    }
 
-      self.info(format!("keeping current {:?}", key));
+    // only the changes that could not be undone are left for the next undo
+    if !self.file_actions.settings.dry_run {
+      failed.reverse();
+      journal::rewrite_journal(&path, &failed)?;
     }
+    self.summary()
   }
 
-  pub fn execute(&mut self) {
-    if self.file_actions.settings.down {
-      self.trace(format!("Down\r\n{:?}", self.file_actions.settings));
+  fn summary(&mut self) -> Result<(), ParoError> {
+    if self.report.errors.is_empty() {
+      return Ok(());
+    }
+
+    self.writeln(format!("{} failures:", self.report.errors.len()));
+    for i in 0..self.report.errors.len() {
+      self.writeln(format!("  {}", self.report.errors[i]));
+    }
+    Err(ParoError::Failures(std::mem::take(&mut self.report.errors)))
+  }
+
+  fn perform(&mut self) -> Result<(), ParoError> {
+    if self.file_actions.settings.conflict_policy() == ConflictPolicy::Fail {
+      let conflicts = Plan::new(&self.file_actions)?.conflicts();
+      if !conflicts.is_empty() {
+        // the user still needs to see which paths are in the way
+        self
+          .report
+          .errors
+          .extend(conflicts.into_iter().map(ParoError::Conflict));
+        return self.summary();
+      }
+    }
+
+    if self.file_actions.settings.operation == Operation::Down {
+      self.trace("Down".to_string());
+      self.trace(format!("{:?}", self.file_actions.settings));
+      self.hooks(Hook::PreDown)?;
       self.down();
+      self.save_state();
+      self.hooks(Hook::PostDown)?;
     } else {
-      self.trace(format!("Up\r\n{:?}", self.file_actions.settings));
+      self.trace("Up".to_string());
+      self.trace(format!("{:?}", self.file_actions.settings));
+      self.hooks(Hook::PreUp)?;
       self.up();
+      let synced = if self.file_actions.settings.removes_stale_links() {
+        self.trace("Sync".to_string());
+        self.sync()
+      } else {
+        Ok(())
+      };
+      // what was installed is recorded even when sync or a post hook fails
+      self.save_state();
+      synced?;
+      self.hooks(Hook::PostUp)?;
     }
+    Ok(())
+  }
+
+  // the state file records what is installed for down and sync --from-state
+  fn save_state(&mut self) {
+    let settings = &self.file_actions.settings;
+    if settings.dry_run {
+      return;
+    }
+
+    let state_dir = settings.state_dir.clone();
+    let timestamp = backup::timestamp(SystemTime::now());
+    let result = Manifest::read(&state_dir).and_then(|manifest| {
+      manifest
+        .unwrap_or_default()
+        .update(&self.file_actions, timestamp)
+        .write(&state_dir)
+    });
+    if let Err(err) = result {
+      self.fail(err);
+    }
+  }
+
+  pub fn execute(&mut self) -> Result<(), ParoError> {
+    self.perform()?;
+    self.summary()
+  }
+
+  pub fn apply(mut self) -> Result<Report, ParoError> {
+    self.perform()?;
+    Ok(self.report)
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::{files, parsers::clap::ClapParser};
+  use crate::{files, parsers::clap::ClapParser, settings::Settings};
+  use std::cell::RefCell;
+  use std::rc::Rc;
   use std::{fs, path::PathBuf};
   use walkdir::WalkDir;
 
@@ -190,13 +888,21 @@ mod tests {
     if let Err(err) = fs::remove_dir_all(test_dir().as_path()) {
       print!("ERROR: {} {:?}\r\n", err, test_dir().as_path())
     }
+    let _ = fs::remove_dir_all("tests/destination-state");
+  }
+
+  fn try_setup(args: Vec<&str>) -> Result<(), ParoError> {
+    files::create_dir(test_dir().as_path())?;
+    let settings = Settings {
+      state_dir: "tests/destination-state".to_string(),
+      ..ClapParser::new().into_settings(args)?
+    };
+    let files_actions: FileActions = FileActions::new(settings).build()?;
+    Actions::new(files_actions).execute()
   }
 
   fn setup(args: Vec<&str>) {
-    files::create_dir(test_dir().as_path());
-    let settings = ClapParser::new().into_settings(args);
-    let files_actions: FileActions = FileActions::new(settings).build();
-    Actions::new(files_actions).execute();
+    try_setup(args).unwrap();
   }
 
   fn base_test(args: Vec<&str>, up_result: Vec<&str>, down_result: Vec<&str>) {
@@ -230,11 +936,13 @@ mod tests {
       ],
       vec![
         "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
         "tests/destination/.folder",
         "tests/destination/.folder/something.txt",
         "tests/destination/.normal-file.txt",
       ],
-      vec!["tests/destination", "tests/destination/.folder"],
+      vec!["tests/destination"],
     );
 
     // adds ignored file
@@ -246,16 +954,18 @@ mod tests {
         "-n",
         "tests/destination",
         "-i",
-        "tests/example-dotfiles/.ignored-file",
+        ".ignored-file",
       ],
       vec![
         "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
         "tests/destination/.folder",
         "tests/destination/.folder/something.txt",
         "tests/destination/.ignored-file",
         "tests/destination/.normal-file.txt",
       ],
-      vec!["tests/destination", "tests/destination/.folder"],
+      vec!["tests/destination"],
     );
 
     // removes file
@@ -267,14 +977,16 @@ mod tests {
         "-n",
         "tests/destination",
         "-x",
-        "tests/example-dotfiles/normal-file.txt",
+        "/normal-file.txt",
       ],
       vec![
         "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
         "tests/destination/.folder",
         "tests/destination/.folder/something.txt",
       ],
-      vec!["tests/destination", "tests/destination/.folder"],
+      vec!["tests/destination"],
     );
 
     // using hostname
@@ -290,12 +1002,14 @@ mod tests {
       ],
       vec![
         "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
         "tests/destination/.file.txt",
         "tests/destination/.folder",
         "tests/destination/.folder/something.txt",
         "tests/destination/.normal-file.txt",
       ],
-      vec!["tests/destination", "tests/destination/.folder"],
+      vec!["tests/destination"],
     );
 
     //  using tag
@@ -311,13 +1025,272 @@ mod tests {
       ],
       vec![
         "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
         "tests/destination/.file.txt",
         "tests/destination/.file1.txt",
         "tests/destination/.folder",
         "tests/destination/.folder/something.txt",
         "tests/destination/.normal-file.txt",
       ],
-      vec!["tests/destination", "tests/destination/.folder"],
+      vec!["tests/destination"],
+    );
+
+    // using copy should not create symlinks
+    delete_test_dir();
+    setup(vec![
+      "paro",
+      "-a",
+      "tests/example-dotfiles",
+      "-n",
+      "tests/destination",
+      "-C",
+    ]);
+    assert!(!fs::symlink_metadata("tests/destination/.normal-file.txt")
+      .unwrap()
+      .file_type()
+      .is_symlink());
+
+    // using include copy should only copy the matching files
+    delete_test_dir();
+    setup(vec![
+      "paro",
+      "-a",
+      "tests/example-dotfiles",
+      "-n",
+      "tests/destination",
+      "-c",
+      "normal-file.txt$",
+    ]);
+    assert!(!fs::symlink_metadata("tests/destination/.normal-file.txt")
+      .unwrap()
+      .file_type()
+      .is_symlink());
+    assert!(
+      fs::symlink_metadata("tests/destination/.folder/something.txt")
+        .unwrap()
+        .file_type()
+        .is_symlink()
+    );
+
+    base_test(
+      vec![
+        "paro",
+        "-a",
+        "tests/example-dotfiles",
+        "-n",
+        "tests/destination",
+        "-C",
+      ],
+      vec![
+        "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
+        "tests/destination/.folder",
+        "tests/destination/.folder/something.txt",
+        "tests/destination/.normal-file.txt",
+      ],
+      vec!["tests/destination"],
+    );
+
+    // using sync should remove links of excluded files
+    delete_test_dir();
+    setup(vec![
+      "paro",
+      "-a",
+      "tests/example-dotfiles",
+      "-n",
+      "tests/destination",
+    ]);
+    setup(vec![
+      "paro",
+      "-a",
+      "tests/example-dotfiles",
+      "-n",
+      "tests/destination",
+      "-x",
+      "normal-file.txt",
+      "-S",
+      "-f",
+    ]);
+    assert_eq!(
+      list_test_dir(),
+      vec![
+        "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
+        "tests/destination/.folder",
+        "tests/destination/.folder/something.txt",
+      ]
+    );
+
+    // using sync should also find links inside an excluded folder
+    setup(vec![
+      "paro",
+      "-a",
+      "tests/example-dotfiles",
+      "-n",
+      "tests/destination",
+      "-x",
+      "folder",
+      "-S",
+      "-f",
+    ]);
+    assert_eq!(
+      list_test_dir(),
+      vec![
+        "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
+        "tests/destination/.folder",
+        "tests/destination/.normal-file.txt",
+      ]
+    );
+
+    // using hooks should abort down when the pre-down hook fails
+    delete_test_dir();
+    setup(vec![
+      "paro",
+      "-a",
+      "tests/example-hooks",
+      "-n",
+      "tests/destination",
+    ]);
+    assert_eq!(
+      list_test_dir(),
+      vec!["tests/destination", "tests/destination/.hook-file.txt"]
+    );
+    assert!(try_setup(vec![
+      "paro",
+      "-a",
+      "tests/example-hooks",
+      "-n",
+      "tests/destination",
+      "--down",
+    ])
+    .is_err());
+    assert_eq!(
+      list_test_dir(),
+      vec!["tests/destination", "tests/destination/.hook-file.txt"]
+    );
+
+    // using skip hooks should not abort down
+    setup(vec![
+      "paro",
+      "-a",
+      "tests/example-hooks",
+      "-n",
+      "tests/destination",
+      "--down",
+      "-K",
+    ]);
+    assert_eq!(list_test_dir(), vec!["tests/destination"]);
+
+    // using no hide should keep the top level name
+    base_test(
+      vec![
+        "paro",
+        "-a",
+        "tests/example-dotfiles",
+        "-n",
+        "tests/destination",
+        "-N",
+        "example-dotfiles/bin$",
+      ],
+      vec![
+        "tests/destination",
+        "tests/destination/.folder",
+        "tests/destination/.folder/something.txt",
+        "tests/destination/.normal-file.txt",
+        "tests/destination/bin",
+        "tests/destination/bin/paro-script",
+      ],
+      vec!["tests/destination"],
+    );
+
+    // using symlink dirs should link the folder as a whole
+    base_test(
+      vec![
+        "paro",
+        "-a",
+        "tests/example-dotfiles",
+        "-n",
+        "tests/destination",
+        "-s",
+        "example-dotfiles/folder$",
+      ],
+      vec![
+        "tests/destination",
+        "tests/destination/.bin",
+        "tests/destination/.bin/paro-script",
+        "tests/destination/.folder",
+        "tests/destination/.normal-file.txt",
+      ],
+      vec!["tests/destination"],
+    );
+
+    // using symlink dirs should replace a folder installed file by file
+    delete_test_dir();
+    let args = vec![
+      "paro",
+      "-a",
+      "tests/example-dotfiles",
+      "-n",
+      "tests/destination",
+    ];
+    setup(args.clone());
+    for extra in [vec!["-f"], vec!["-f", "--no-backup"]] {
+      setup(
+        [args.clone(), vec!["-s", "example-dotfiles/folder$"], extra].concat(),
+      );
+      assert!(fs::symlink_metadata("tests/destination/.folder")
+        .unwrap()
+        .file_type()
+        .is_symlink());
+      fs::remove_file("tests/destination/.folder").unwrap();
+      setup(args.clone());
+    }
+
+    // even without backups a replaced folder is moved away, not deleted
+    fs::write("tests/destination/.folder/unmanaged.txt", "local").unwrap();
+    setup(
+      [
+        args.clone(),
+        vec!["-s", "example-dotfiles/folder$", "-f", "--no-backup"],
+      ]
+      .concat(),
+    );
+    assert!(fs::symlink_metadata("tests/destination/.folder")
+      .unwrap()
+      .file_type()
+      .is_symlink());
+    let backup =
+      crate::backup::latest_backup_dir("tests/destination-state").unwrap();
+    assert_eq!(
+      fs::read_to_string(backup.join(".folder/unmanaged.txt")).unwrap(),
+      "local"
+    );
+    fs::remove_file("tests/destination/.folder").unwrap();
+
+    // folders holding unmanaged files are kept on down
+    delete_test_dir();
+    let args = vec![
+      "paro",
+      "-a",
+      "tests/example-dotfiles",
+      "-n",
+      "tests/destination",
+    ];
+    setup(args.clone());
+    fs::write("tests/destination/.folder/unmanaged.txt", "local").unwrap();
+    setup([args, vec!["--down"]].concat());
+    assert_eq!(
+      list_test_dir(),
+      vec![
+        "tests/destination",
+        "tests/destination/.folder",
+        "tests/destination/.folder/unmanaged.txt",
+      ]
     );
 
     // using dry_run
@@ -338,4 +1311,497 @@ mod tests {
 
     delete_test_dir()
   }
+
+  // the destination and state folders of one test, removed even on failure
+  struct Fixture {
+    home: PathBuf,
+    state: PathBuf,
+    dotfiles: PathBuf,
+  }
+
+  impl Fixture {
+    fn new(name: &str) -> Self {
+      let fixture = Self {
+        home: PathBuf::from(format!("tests/{}-destination", name)),
+        state: PathBuf::from(format!("tests/{}-state", name)),
+        dotfiles: PathBuf::from(format!("tests/{}-dotfiles", name)),
+      };
+      fixture.reset();
+      fixture
+    }
+
+    fn reset(&self) {
+      self.clean();
+      fs::create_dir_all(&self.home).unwrap();
+    }
+
+    fn clean(&self) {
+      let _ = fs::remove_dir_all(&self.home);
+      let _ = fs::remove_dir_all(&self.state);
+      let _ = fs::remove_dir_all(&self.dotfiles);
+    }
+
+    fn write(&self, path: &str, content: &str) {
+      let path = self.home.join(path);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, content).unwrap();
+    }
+
+    fn settings(&self) -> Settings {
+      Settings {
+        directories: vec!["tests/example-dotfiles".to_string()],
+        destination: self.home.to_string_lossy().to_string(),
+        state_dir: self.state.to_string_lossy().to_string(),
+        ..Default::default()
+      }
+    }
+
+    fn files(&self) -> Vec<String> {
+      WalkDir::new(&self.home)
+        .min_depth(1)
+        .into_iter()
+        .map(|e| e.unwrap().path().to_string_lossy().to_string())
+        .collect()
+    }
+  }
+
+  impl Drop for Fixture {
+    fn drop(&mut self) {
+      self.clean();
+    }
+  }
+
+  fn try_add(args: Vec<&str>) -> Result<(), ParoError> {
+    let settings = Settings {
+      state_dir: "tests/add-state".to_string(),
+      ..ClapParser::new().into_settings(args)?
+    };
+    Actions::new(FileActions::new(settings)).add()
+  }
+
+  #[test]
+  fn add_test() {
+    let fixture = Fixture::new("add");
+    let (home, dotfiles) = (&fixture.home, &fixture.dotfiles);
+    fixture.write(".vimrc", "set number");
+    fixture.write(".config/app.toml", "key=1");
+    let args = vec![
+      "paro",
+      "-a",
+      "tests/add-dotfiles",
+      "-n",
+      "tests/add-destination",
+    ];
+
+    // dry run should not move anything
+    let mut dry_run = args.clone();
+    dry_run.extend(["-D", "add", "tests/add-destination/.vimrc"]);
+    try_add(dry_run).unwrap();
+    assert!(!dotfiles.exists());
+
+    let mut add = args.clone();
+    add.extend(["add", "tests/add-destination/.vimrc"]);
+    try_add(add.clone()).unwrap();
+    assert!(home.join(".vimrc").is_symlink());
+    assert_eq!(
+      fs::read_to_string(dotfiles.join("vimrc")).unwrap(),
+      "set number"
+    );
+
+    // tagged files go to the tag folder
+    let mut tagged = args.clone();
+    tagged.extend([
+      "-t",
+      "work",
+      "add",
+      "tests/add-destination/.config/app.toml",
+    ]);
+    try_add(tagged).unwrap();
+    assert!(home.join(".config/app.toml").is_symlink());
+    assert!(dotfiles.join("tag-work/config/app.toml").is_file());
+
+    // files already linked are not added again
+    assert_eq!(try_add(add).unwrap_err().exit_code(), 2);
+
+    // top level names without a dot would come back hidden
+    fixture.write("bin/script", "echo");
+    let mut not_hidden = args.clone();
+    not_hidden.extend(["add", "tests/add-destination/bin/script"]);
+    assert_eq!(try_add(not_hidden.clone()).unwrap_err().exit_code(), 2);
+    assert!(!home.join("bin/script").is_symlink());
+    not_hidden.splice(1..1, ["-N", "add-dotfiles/bin$"]);
+    try_add(not_hidden).unwrap();
+    assert!(dotfiles.join("bin/script").is_file());
+
+    // without dotfiles directory there is nowhere to add to
+    let settings = Settings {
+      add: vec!["tests/add-destination/.config".to_string()],
+      ..Default::default()
+    };
+    let error = Actions::headless(FileActions::new(settings)).add();
+    assert_eq!(error.unwrap_err().exit_code(), 2);
+  }
+
+  #[test]
+  fn headless_test() {
+    let fixture = Fixture::new("headless");
+    let home = &fixture.home;
+    fixture.write(".normal-file.txt", "local changes");
+
+    let file_actions = FileActions::new(Settings {
+      directories: vec![
+        "tests/example-dotfiles".to_string(),
+        "tests/missing-dotfiles".to_string(),
+      ],
+      excludes: vec!["bin".to_string()],
+      ..fixture.settings()
+    })
+    .build()
+    .unwrap();
+    let report = Actions::headless(file_actions).apply().unwrap();
+
+    assert_eq!(
+      report.entries,
+      vec![
+        ReportEntry {
+          destination: home.join(".folder"),
+          action: Action::Mkdir,
+        },
+        ReportEntry {
+          destination: home.join(".folder/something.txt"),
+          action: Action::Link,
+        },
+        ReportEntry {
+          destination: home.join(".normal-file.txt"),
+          action: Action::Conflict,
+        },
+      ]
+    );
+    // the conflict and the missing dotfiles folder
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!(
+      fs::read_to_string(home.join(".normal-file.txt")).unwrap(),
+      "local changes"
+    );
+  }
+
+  #[test]
+  fn conflict_policy_test() {
+    let fixture = Fixture::new("conflict");
+    let home = &fixture.home;
+    let apply = |conflict: ConflictPolicy| {
+      fixture.reset();
+      fixture.write(".normal-file.txt", "local changes");
+      let file_actions = FileActions::new(Settings {
+        excludes: vec!["bin".to_string(), "folder".to_string()],
+        conflict,
+        no_backup: true,
+        ..fixture.settings()
+      })
+      .build()
+      .unwrap();
+      let report = Actions::headless(file_actions).apply().unwrap();
+      // backup keeps the replaced file even with no_backup
+      let backup = crate::backup::latest_backup_dir("tests/conflict-state")
+        .map(|dir| fs::read_to_string(dir.join(".normal-file.txt")));
+      if conflict == ConflictPolicy::Backup {
+        assert_eq!(backup.unwrap().unwrap(), "local changes");
+      } else {
+        assert!(backup.is_none());
+      }
+      (report, home.join(".normal-file.txt").is_symlink())
+    };
+
+    // failing stops before changing anything
+    fixture.write(".normal-file.txt", "local changes");
+    let file_actions = FileActions::new(Settings {
+      conflict: ConflictPolicy::Fail,
+      ..fixture.settings()
+    })
+    .build()
+    .unwrap();
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let err = Actions {
+      console: Some(Box::new(Scripted {
+        answers: vec![],
+        lines: lines.clone(),
+      })),
+      ..Actions::headless(file_actions)
+    }
+    .execute()
+    .unwrap_err();
+    assert_eq!(err.exit_code(), 3);
+    assert!(!home.join(".folder").exists());
+    assert_eq!(
+      *lines.borrow(),
+      [
+        "1 failures:".to_string(),
+        format!("  {}", ParoError::Conflict(home.join(".normal-file.txt"))),
+      ]
+    );
+
+    // skipping is not a failure
+    let (report, linked) = apply(ConflictPolicy::Skip);
+    assert!(!linked);
+    assert!(report.errors.is_empty());
+    assert_eq!(report.entries.last().unwrap().action, Action::Skip);
+
+    let (report, linked) = apply(ConflictPolicy::Overwrite);
+    assert!(linked);
+    assert!(report.errors.is_empty());
+    assert_eq!(report.entries.last().unwrap().action, Action::Overwrite);
+
+    let (report, linked) = apply(ConflictPolicy::Backup);
+    assert!(linked);
+    assert!(report.errors.is_empty());
+    assert_eq!(report.entries.last().unwrap().action, Action::Backup);
+  }
+
+  #[test]
+  fn restore_test() {
+    let fixture = Fixture::new("restore");
+    let home = &fixture.home;
+    fixture.write(".normal-file.txt", "local changes");
+    fixture.write(".folder/something.txt", "local folder");
+    let settings = Settings {
+      excludes: vec!["bin".to_string()],
+      force: true,
+      ..fixture.settings()
+    };
+
+    // overwriting moves the replaced files into a timestamped backup
+    let file_actions = FileActions::new(settings.clone()).build().unwrap();
+    Actions::headless(file_actions).apply().unwrap();
+    assert!(home.join(".normal-file.txt").is_symlink());
+    let backup_dir =
+      crate::backup::latest_backup_dir("tests/restore-state").unwrap();
+    assert_eq!(
+      fs::read_to_string(backup_dir.join(".folder/something.txt")).unwrap(),
+      "local folder"
+    );
+
+    // restoring puts them back in place of the links
+    let mut actions = Actions::headless(FileActions::new(Settings {
+      operation: Operation::Restore,
+      ..settings
+    }));
+    actions.restore().unwrap();
+    assert_eq!(actions.report.entries.len(), 2);
+    assert_eq!(
+      fs::read_to_string(home.join(".normal-file.txt")).unwrap(),
+      "local changes"
+    );
+    assert_eq!(
+      fs::read_to_string(home.join(".folder/something.txt")).unwrap(),
+      "local folder"
+    );
+    assert!(!backup_dir.exists());
+  }
+
+  #[test]
+  fn restore_symlink_dir_test() {
+    let fixture = Fixture::new("restore-dir");
+    let home = &fixture.home;
+    fixture.write(".folder/something.txt", "mine");
+    let settings = Settings {
+      excludes: vec!["bin".to_string()],
+      symlink_dirs: vec!["example-dotfiles/folder$".to_string()],
+      force: true,
+      ..fixture.settings()
+    };
+    let dotfile =
+      fs::read_to_string("tests/example-dotfiles/folder/something.txt")
+        .unwrap();
+
+    let file_actions = FileActions::new(settings.clone()).build().unwrap();
+    Actions::headless(file_actions).apply().unwrap();
+    assert!(home.join(".folder").is_symlink());
+
+    // the folder replaces the link instead of being written through it
+    let mut actions = Actions::headless(FileActions::new(Settings {
+      operation: Operation::Restore,
+      ..settings
+    }));
+    actions.restore().unwrap();
+    assert!(!home.join(".folder").is_symlink());
+    assert_eq!(
+      fs::read_to_string(home.join(".folder/something.txt")).unwrap(),
+      "mine"
+    );
+    assert_eq!(
+      fs::read_to_string("tests/example-dotfiles/folder/something.txt")
+        .unwrap(),
+      dotfile
+    );
+    assert_eq!(
+      actions.report.entries,
+      [ReportEntry {
+        destination: home.join(".folder"),
+        action: Action::Restore,
+      }]
+    );
+  }
+
+  #[test]
+  fn post_hook_state_test() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let fixture = Fixture::new("post-hook");
+    let hook = fixture.dotfiles.join("hooks/post-up");
+    fs::create_dir_all(hook.parent().unwrap()).unwrap();
+    fs::write(fixture.dotfiles.join("vimrc"), "set number").unwrap();
+    fs::write(&hook, "#!/bin/sh\nexit 1\n").unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    let settings = |operation: Operation| Settings {
+      directories: vec!["tests/post-hook-dotfiles".to_string()],
+      from_state: true,
+      operation,
+      ..fixture.settings()
+    };
+    let apply = |settings: Settings| {
+      let file_actions = FileActions::new(settings).build().unwrap();
+      Actions::headless(file_actions).apply()
+    };
+
+    // the links made before the failing hook are still in the state file
+    assert!(apply(settings(Operation::Up)).is_err());
+    assert!(fixture.home.join(".vimrc").is_symlink());
+    let manifest = Manifest::read_required("tests/post-hook-state").unwrap();
+    assert_eq!(manifest.entries.len(), 1);
+
+    apply(settings(Operation::Down)).unwrap();
+    assert!(fixture.files().is_empty());
+  }
+
+  struct Scripted {
+    answers: Vec<Inputs>,
+    lines: Rc<RefCell<Vec<String>>>,
+  }
+
+  impl Console for Scripted {
+    fn writeln(&mut self, message: String) {
+      self.lines.borrow_mut().push(message);
+    }
+
+    fn dialog(&mut self, question: String, _: &[Inputs]) -> Inputs {
+      self.lines.borrow_mut().push(question);
+      self.answers.remove(0)
+    }
+  }
+
+  #[test]
+  fn dialog_test() {
+    let fixture = Fixture::new("dialog");
+    let home = &fixture.home;
+    fixture.write(".normal-file.txt", "local changes");
+    fixture.write(".folder/something.txt", "local folder");
+    let file_actions = FileActions::new(Settings {
+      excludes: vec!["bin".to_string()],
+      ..fixture.settings()
+    })
+    .build()
+    .unwrap();
+    let lines = Rc::new(RefCell::new(Vec::new()));
+
+    // the diff asks again and yes to all answers the remaining files
+    let report = Actions {
+      console: Some(Box::new(Scripted {
+        answers: vec![Inputs::Diff, Inputs::YesAll],
+        lines: lines.clone(),
+      })),
+      ..Actions::headless(file_actions)
+    }
+    .apply()
+    .unwrap();
+
+    assert!(home.join(".normal-file.txt").is_symlink());
+    assert!(home.join(".folder/something.txt").is_symlink());
+    assert!(report.errors.is_empty());
+    let lines = lines.borrow();
+    assert_eq!(
+      lines.iter().filter(|l| l.starts_with("Overwrite?")).count(),
+      2
+    );
+    assert!(lines.contains(&"-local folder".to_string()));
+    assert!(lines.contains(
+      &"+++ tests/example-dotfiles/folder/something.txt".to_string()
+    ));
+  }
+
+  #[test]
+  fn undo_test() {
+    let fixture = Fixture::new("undo-actions");
+    let home = &fixture.home;
+    fixture.write(".normal-file.txt", "local changes");
+    let settings = Settings {
+      tags: vec!["um".to_string()],
+      force: true,
+      ..fixture.settings()
+    };
+    let undo = || {
+      let mut actions = Actions::headless(FileActions::new(Settings {
+        operation: Operation::Undo,
+        ..settings.clone()
+      }));
+      actions.undo()
+    };
+
+    let file_actions = FileActions::new(settings.clone()).build().unwrap();
+    Actions::headless(file_actions).apply().unwrap();
+    assert!(home.join(".file.txt").is_symlink());
+
+    // a changed file keeps only its own change in the journal
+    fs::remove_file(home.join(".file.txt")).unwrap();
+    fixture.write(".file.txt", "local file");
+    assert_eq!(undo().unwrap_err().exit_code(), 3);
+    let journal = journal::latest_journal("tests/undo-actions-state").unwrap();
+    assert!(matches!(
+      journal::read_journal(&journal).unwrap().as_slice(),
+      [Change::Link { path, .. }]
+        if *path == std::path::absolute(home.join(".file.txt")).unwrap()
+    ));
+    assert_eq!(
+      fs::read_to_string(home.join(".normal-file.txt")).unwrap(),
+      "local changes"
+    );
+
+    fs::remove_file(home.join(".file.txt")).unwrap();
+    undo().unwrap();
+    assert_eq!(
+      fixture.files(),
+      vec!["tests/undo-actions-destination/.normal-file.txt"]
+    );
+    assert_eq!(
+      fs::read_to_string(home.join(".normal-file.txt")).unwrap(),
+      "local changes"
+    );
+    // the journal is dropped once undone
+    assert_eq!(undo().unwrap_err().exit_code(), 2);
+  }
+
+  #[test]
+  fn from_state_test() {
+    let fixture = Fixture::new("from-state");
+    let settings = |tag: &str, operation: Operation| Settings {
+      excludes: vec!["bin".to_string(), "folder".to_string()],
+      tags: vec![tag.to_string()],
+      from_state: true,
+      operation,
+      ..fixture.settings()
+    };
+    let apply = |settings: Settings| {
+      let file_actions = FileActions::new(settings).build().unwrap();
+      Actions::headless(file_actions).apply().unwrap()
+    };
+
+    apply(settings("um", Operation::Up));
+    assert!(fixture.home.join(".file1.txt").is_symlink());
+
+    // down from the state removes the links of the tag used by up
+    let report = apply(settings("dois", Operation::Down));
+    assert_eq!(report.entries.len(), 3);
+    assert!(fixture.files().is_empty());
+    let manifest = Manifest::read_required("tests/from-state-state").unwrap();
+    assert!(manifest.entries.is_empty());
+  }
 }
@@ -0,0 +1,189 @@
+use crate::journal;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const BACKUPS_DIR: &str = "backups";
+const TIMESTAMP_LEN: usize = "1970-01-01T00-00-00".len();
+
+// days since the unix epoch to a (year, month, day) in the gregorian calendar
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  (year, month, day)
+}
+
+pub fn timestamp(time: SystemTime) -> String {
+  let seconds = time
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs() as i64)
+    .unwrap_or(0);
+  let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+  let seconds = seconds.rem_euclid(86400);
+  format!(
+    "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+    year,
+    month,
+    day,
+    seconds / 3600,
+    seconds % 3600 / 60,
+    seconds % 60
+  )
+}
+
+// runs in the same second get a -1, -2... suffix, so -10 comes after -9
+pub fn run_order(name: &str) -> (String, u64) {
+  match name.get(TIMESTAMP_LEN..).and_then(|s| s.strip_prefix('-')) {
+    Some(count) => (
+      name[..TIMESTAMP_LEN].to_string(),
+      count.parse().unwrap_or(0),
+    ),
+    None => (name.to_string(), 0),
+  }
+}
+
+pub fn backups_dir(state_dir: &str) -> PathBuf {
+  Path::new(state_dir).join(BACKUPS_DIR)
+}
+
+// the journal is written even by runs that back nothing up, so a name is only
+// free when neither its backup folder nor its journal exists
+pub fn new_backup_dir(state_dir: &str, time: SystemTime) -> PathBuf {
+  let timestamp = timestamp(time);
+  let mut name = timestamp.clone();
+  let mut count = 0;
+  while backups_dir(state_dir).join(&name).exists()
+    || journal::journal_file(state_dir, &name).exists()
+  {
+    count += 1;
+    name = format!("{}-{}", timestamp, count);
+  }
+  backups_dir(state_dir).join(name)
+}
+
+pub fn latest_backup_dir(state_dir: &str) -> Option<PathBuf> {
+  fs::read_dir(backups_dir(state_dir))
+    .ok()?
+    .filter_map(|e| e.ok())
+    .map(|e| e.path())
+    .filter(|p| p.is_dir())
+    .max_by_key(|p| {
+      run_order(&p.file_name().unwrap_or_default().to_string_lossy())
+    })
+}
+
+pub fn backup_path(
+  backup_dir: &Path,
+  destination: &str,
+  destiny_file: &Path,
+) -> PathBuf {
+  let relative = destiny_file
+    .strip_prefix(destination)
+    .or_else(|_| destiny_file.strip_prefix("/"))
+    .unwrap_or(destiny_file);
+  backup_dir.join(relative)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  #[test]
+  fn test_timestamp() {
+    assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00-00-00");
+    assert_eq!(
+      timestamp(UNIX_EPOCH + Duration::from_secs(1_792_326_896)),
+      "2026-10-18T12-34-56"
+    );
+    assert_eq!(
+      timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+      "2000-02-29T00-00-00"
+    );
+  }
+
+  #[test]
+  fn test_backup_path() {
+    let dir = Path::new("/state/backups/2026-10-18T12-34-56");
+    assert_eq!(
+      backup_path(dir, "/home/user/", Path::new("/home/user/.config/app")),
+      dir.join(".config/app")
+    );
+    assert_eq!(
+      backup_path(dir, "/home/user", Path::new("/etc/app.conf")),
+      dir.join("etc/app.conf")
+    );
+  }
+
+  #[test]
+  fn test_latest_backup_dir() {
+    let state = "tests/backup-state";
+    let _ = fs::remove_dir_all(state);
+    assert_eq!(latest_backup_dir(state), None);
+
+    let first = new_backup_dir(state, UNIX_EPOCH);
+    fs::create_dir_all(&first).unwrap();
+    let second = new_backup_dir(state, UNIX_EPOCH);
+    fs::create_dir_all(&second).unwrap();
+    let latest = latest_backup_dir(state);
+    fs::remove_dir_all(state).unwrap();
+
+    assert_eq!(first, backups_dir(state).join("1970-01-01T00-00-00"));
+    assert_eq!(second, backups_dir(state).join("1970-01-01T00-00-00-1"));
+    assert_eq!(latest, Some(second));
+  }
+
+  #[test]
+  fn test_new_backup_dir_after_journal() {
+    let state = "tests/backup-journal-state";
+    let _ = fs::remove_dir_all(state);
+    let journal = journal::journal_file(state, "1970-01-01T00-00-00");
+    fs::create_dir_all(journal.parent().unwrap()).unwrap();
+    fs::write(&journal, "").unwrap();
+    let dir = new_backup_dir(state, UNIX_EPOCH);
+    fs::remove_dir_all(state).unwrap();
+
+    assert_eq!(dir, backups_dir(state).join("1970-01-01T00-00-00-1"));
+  }
+
+  #[test]
+  fn test_run_order() {
+    assert_eq!(
+      run_order("2026-10-18T12-34-56"),
+      ("2026-10-18T12-34-56".to_string(), 0)
+    );
+    assert_eq!(
+      run_order("2026-10-18T12-34-56-10"),
+      ("2026-10-18T12-34-56".to_string(), 10)
+    );
+    assert!(
+      run_order("2026-10-18T12-34-56-9") < run_order("2026-10-18T12-34-56-10")
+    );
+    assert!(
+      run_order("2026-10-18T12-34-56-10") < run_order("2026-10-18T12-34-57")
+    );
+  }
+
+  #[test]
+  fn test_latest_backup_dir_many_runs() {
+    let state = "tests/backup-many-state";
+    let _ = fs::remove_dir_all(state);
+    for _ in 0..11 {
+      fs::create_dir_all(new_backup_dir(state, UNIX_EPOCH)).unwrap();
+    }
+    let latest = latest_backup_dir(state);
+    fs::remove_dir_all(state).unwrap();
+
+    assert_eq!(
+      latest,
+      Some(backups_dir(state).join("1970-01-01T00-00-00-10"))
+    );
+  }
+}
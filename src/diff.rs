@@ -0,0 +1,162 @@
+use std::cmp::max;
+
+const CONTEXT: usize = 3;
+// the lcs table grows with the product of the number of lines
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+  Equal,
+  Delete,
+  Insert,
+}
+
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+  let (n, m) = (old.len(), new.len());
+  // lcs[i][j] is the longest common subsequence of old[i..] and new[j..]
+  let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+  for i in (0..n).rev() {
+    for j in (0..m).rev() {
+      lcs[i][j] = if old[i] == new[j] {
+        lcs[i + 1][j + 1] + 1
+      } else {
+        max(lcs[i + 1][j], lcs[i][j + 1])
+      };
+    }
+  }
+
+  let (mut i, mut j) = (0, 0);
+  let mut edits = Vec::new();
+  while i < n || j < m {
+    if i < n && j < m && old[i] == new[j] {
+      edits.push(Edit::Equal);
+      i += 1;
+      j += 1;
+    } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+      edits.push(Edit::Delete);
+      i += 1;
+    } else {
+      edits.push(Edit::Insert);
+      j += 1;
+    }
+  }
+  edits
+}
+
+fn range(start: usize, len: usize) -> String {
+  match len {
+    0 => format!("{},0", start),
+    1 => format!("{}", start + 1),
+    _ => format!("{},{}", start + 1, len),
+  }
+}
+
+pub fn unified(
+  old_text: &str,
+  new_text: &str,
+  old_name: &str,
+  new_name: &str,
+) -> Vec<String> {
+  let old: Vec<&str> = old_text.lines().collect();
+  let new: Vec<&str> = new_text.lines().collect();
+  if old == new {
+    return Vec::new();
+  }
+  if old.len().saturating_mul(new.len()) > MAX_CELLS {
+    return vec![format!("Files {} and {} differ", old_name, new_name)];
+  }
+
+  let edits = edits(&old, &new);
+  // line of each file before every edit
+  let mut positions = Vec::with_capacity(edits.len() + 1);
+  let (mut i, mut j) = (0, 0);
+  for edit in &edits {
+    positions.push((i, j));
+    match edit {
+      Edit::Equal => {
+        i += 1;
+        j += 1;
+      }
+      Edit::Delete => i += 1,
+      Edit::Insert => j += 1,
+    }
+  }
+  positions.push((i, j));
+
+  let changes: Vec<usize> = (0..edits.len())
+    .filter(|&k| edits[k] != Edit::Equal)
+    .collect();
+  let mut lines =
+    vec![format!("--- {}", old_name), format!("+++ {}", new_name)];
+  let mut k = 0;
+  while k < changes.len() {
+    let start = changes[k].saturating_sub(CONTEXT);
+    let mut end = changes[k] + 1;
+    // changes closer than twice the context share the same hunk
+    while k + 1 < changes.len() && changes[k + 1] <= end + 2 * CONTEXT {
+      k += 1;
+      end = changes[k] + 1;
+    }
+    let end = (end + CONTEXT).min(edits.len());
+    k += 1;
+
+    let (old_start, new_start) = positions[start];
+    let (old_end, new_end) = positions[end];
+    lines.push(format!(
+      "@@ -{} +{} @@",
+      range(old_start, old_end - old_start),
+      range(new_start, new_end - new_start)
+    ));
+    for (edit, &(i, j)) in edits[start..end].iter().zip(&positions[start..end])
+    {
+      lines.push(match edit {
+        Edit::Equal => format!(" {}", old[i]),
+        Edit::Delete => format!("-{}", old[i]),
+        Edit::Insert => format!("+{}", new[j]),
+      });
+    }
+  }
+  lines
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_unified() {
+    assert!(unified("same\n", "same\n", "a", "b").is_empty());
+
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let new = "1\n2\n3\nfour\n5\n6\n7\n8\n9\n10\n11\n12\n13\n";
+    assert_eq!(
+      unified(old, new, "current", "dotfile"),
+      vec![
+        "--- current",
+        "+++ dotfile",
+        "@@ -1,7 +1,7 @@",
+        " 1",
+        " 2",
+        " 3",
+        "-4",
+        "+four",
+        " 5",
+        " 6",
+        " 7",
+        "@@ -10,3 +10,4 @@",
+        " 10",
+        " 11",
+        " 12",
+        "+13",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_unified_empty_file() {
+    assert_eq!(
+      unified("", "set number\n", "current", "dotfile"),
+      vec!["--- current", "+++ dotfile", "@@ -0,0 +1 @@", "+set number"]
+    );
+  }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ParoError {
+  Io { error: io::Error, path: PathBuf },
+  Config(String),
+  Conflict(PathBuf),
+  Hook(String),
+  Drift(usize),
+  Failures(Vec<ParoError>),
+}
+
+impl ParoError {
+  pub fn io(error: io::Error, path: &Path) -> Self {
+    ParoError::Io {
+      error,
+      path: path.to_path_buf(),
+    }
+  }
+
+  pub fn destination(destination: &str, error: io::Error) -> Self {
+    match error.kind() {
+      io::ErrorKind::NotFound => ParoError::Config(format!(
+        "Destination folder {:?} does not exist",
+        destination
+      )),
+      _ => ParoError::Config(format!(
+        "Invalid destination folder {:?}: {}",
+        destination, error
+      )),
+    }
+  }
+
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      ParoError::Io { .. } => 1,
+      ParoError::Config(_) => 2,
+      ParoError::Conflict(_) => 3,
+      ParoError::Hook(_) => 4,
+      ParoError::Drift(_) => 5,
+      ParoError::Failures(errors) => {
+        errors.iter().map(|e| e.exit_code()).min().unwrap_or(1)
+      }
+    }
+  }
+}
+
+// io errors cannot be cloned, their kind and message are kept
+impl Clone for ParoError {
+  fn clone(&self) -> Self {
+    match self {
+      ParoError::Io { error, path } => ParoError::Io {
+        error: io::Error::new(error.kind(), error.to_string()),
+        path: path.clone(),
+      },
+      ParoError::Config(message) => ParoError::Config(message.clone()),
+      ParoError::Conflict(path) => ParoError::Conflict(path.clone()),
+      ParoError::Hook(message) => ParoError::Hook(message.clone()),
+      ParoError::Drift(count) => ParoError::Drift(*count),
+      ParoError::Failures(errors) => ParoError::Failures(errors.clone()),
+    }
+  }
+}
+
+impl fmt::Display for ParoError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ParoError::Io { error, path } => write!(f, "{} {:?}", error, path),
+      ParoError::Config(message) => write!(f, "{}", message),
+      ParoError::Conflict(path) => {
+        write!(f, "conflict not resolved {:?}", path)
+      }
+      ParoError::Hook(message) => write!(f, "{}", message),
+      ParoError::Drift(count) => write!(f, "{} files out of sync", count),
+      ParoError::Failures(errors) => write!(f, "{} failures", errors.len()),
+    }
+  }
+}
+
+impl From<walkdir::Error> for ParoError {
+  fn from(error: walkdir::Error) -> Self {
+    let path = error.path().unwrap_or_else(|| Path::new("")).to_path_buf();
+    let message = error.to_string();
+    ParoError::Io {
+      error: error
+        .into_io_error()
+        .unwrap_or_else(|| io::Error::other(message)),
+      path,
+    }
+  }
+}
+
+impl From<config::ConfigError> for ParoError {
+  fn from(error: config::ConfigError) -> Self {
+    ParoError::Config(error.to_string())
+  }
+}
+
+impl From<regex::Error> for ParoError {
+  fn from(error: regex::Error) -> Self {
+    ParoError::Config(format!("Invalid pattern: {}", error))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_exit_code() {
+    let io_error = ParoError::io(
+      io::Error::from(io::ErrorKind::NotFound),
+      Path::new("file.txt"),
+    );
+    assert_eq!(io_error.exit_code(), 1);
+    assert_eq!(ParoError::Config("".to_string()).exit_code(), 2);
+    assert_eq!(ParoError::Conflict(PathBuf::new()).exit_code(), 3);
+    assert_eq!(ParoError::Hook("".to_string()).exit_code(), 4);
+    assert_eq!(ParoError::Drift(2).exit_code(), 5);
+    assert_eq!(
+      ParoError::Failures(vec![
+        ParoError::Conflict(PathBuf::new()),
+        io_error,
+        ParoError::Conflict(PathBuf::new()),
+      ])
+      .exit_code(),
+      1
+    );
+    assert_eq!(
+      ParoError::Failures(vec![ParoError::Conflict(PathBuf::new())])
+        .exit_code(),
+      3
+    );
+  }
+
+  #[test]
+  fn test_display() {
+    assert_eq!(
+      ParoError::io(
+        io::Error::from(io::ErrorKind::NotFound),
+        Path::new("file.txt")
+      )
+      .to_string(),
+      "entity not found \"file.txt\""
+    );
+    assert_eq!(
+      ParoError::Conflict(PathBuf::from("file.txt")).to_string(),
+      "conflict not resolved \"file.txt\""
+    );
+    assert_eq!(ParoError::Drift(2).to_string(), "2 files out of sync");
+  }
+}
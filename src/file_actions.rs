@@ -1,16 +1,40 @@
-use crate::files;
-use crate::settings::Settings;
+use crate::error::ParoError;
+use crate::manifest::Manifest;
+use crate::patterns::Patterns;
+use crate::settings::{Operation, Settings};
+use crate::{files, hooks};
 use regex::RegexSet;
-use std::collections::BTreeMap;
-use std::fs::FileType;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::{self, FileType};
+use std::path::{Path, PathBuf};
 use walkdir::{DirEntry, WalkDir};
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Method {
+  Link,
+  Copy,
+}
+
+impl fmt::Display for Method {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Method::Link => write!(f, "link"),
+      Method::Copy => write!(f, "copy"),
+    }
+  }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct FileEntry {
   pub path: PathBuf,
   pub file_type: FileType,
   pub depth: isize,
+  pub method: Method,
+  pub symlink_dir: bool,
+  pub special_folder: Option<String>,
 }
 
 type Actions = BTreeMap<PathBuf, FileEntry>;
@@ -19,6 +43,7 @@ type Actions = BTreeMap<PathBuf, FileEntry>;
 pub struct FileActions {
   pub settings: Settings,
   pub actions: Actions,
+  pub errors: Vec<ParoError>,
 }
 
 fn in_special_folder(
@@ -37,11 +62,53 @@ fn in_special_folder(
   }
 }
 
+fn to_action(
+  entry: DirEntry,
+  dir: &String,
+  destination: &String,
+  special_folders: &[String],
+  set: &RegexSet,
+) -> (PathBuf, FileEntry) {
+  match in_special_folder(&entry, special_folders, set) {
+    Some(special_folder) => (
+      files::change_root_dir(
+        entry.path(),
+        &format!("{}/{}", dir, special_folder),
+        destination,
+        false,
+      ),
+      FileEntry {
+        special_folder: Some(special_folder),
+        ..to_file_entry(entry, -1)
+      },
+    ),
+    None => (
+      files::change_root_dir(entry.path(), dir, destination, false),
+      to_file_entry(entry, 0),
+    ),
+  }
+}
+
+// the dotfiles folder a path comes from and the path inside it
+fn relative_path<'a, 'b>(
+  path: &'a Path,
+  directories: &'b [String],
+) -> Option<(&'b String, &'a Path)> {
+  directories
+    .iter()
+    .filter_map(|dir| Some((dir, path.strip_prefix(dir).ok()?)))
+    .filter(|(_dir, relative)| !relative.as_os_str().is_empty())
+    .min_by_key(|(_dir, relative)| relative.components().count())
+}
+
 fn to_file_entry(entry: DirEntry, depth_adjust: isize) -> FileEntry {
   FileEntry {
     path: entry.path().to_path_buf(),
     file_type: entry.file_type(),
     depth: (entry.depth() as isize) + depth_adjust,
+    method: Method::Link,
+    symlink_dir: false,
+    special_folder: None,
   }
 }
 
@@ -50,15 +117,18 @@ impl FileActions {
     Self {
       settings,
       actions: Actions::new(),
+      errors: Vec::new(),
     }
   }
 
-  pub fn select_files(&mut self) {
+  pub fn select_files(&mut self) -> Result<(), ParoError> {
     let special_folders = &self.settings.special_folder_vec();
-    let set = RegexSet::new(special_folders).unwrap();
+    let set = RegexSet::new(special_folders)?;
+    let symlink_dirs = RegexSet::new(&self.settings.symlink_dirs)?;
 
     for dir in &self.settings.directories {
-      let mut entries = WalkDir::new(&dir).into_iter();
+      let ignore = Patterns::read_ignore(dir)?;
+      let mut entries = WalkDir::new(dir).into_iter();
       loop {
         match entries.next() {
           None => break,
@@ -70,79 +140,182 @@ impl FileActions {
               continue;
             }
 
-            if let Some(special_folder) =
-              in_special_folder(&entry, special_folders, &set)
+            if entry.depth() == 1
+              && entry.file_type().is_dir()
+              && entry.file_name() == hooks::HOOKS_DIR
             {
-              self.actions.insert(
-                files::change_root_dir(
-                  entry.path(),
-                  &format!("{}/{}", dir, special_folder),
-                  &self.settings.destination,
-                  false,
-                ),
-                to_file_entry(entry, -1),
-              );
+              entries.skip_current_dir();
               continue;
             }
 
+            // like in git, !pattern cannot take back files of an ignored folder
+            let relative =
+              entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            if entry.depth() > 0
+              && ignore.is_match(relative, entry.file_type().is_dir())
+            {
+              if entry.file_type().is_dir() {
+                entries.skip_current_dir();
+              }
+              continue;
+            }
+
+            let symlink_dir = entry.depth() > 0
+              && entry.file_type().is_dir()
+              && symlink_dirs.is_match(entry.path().to_str().unwrap());
+            if symlink_dir {
+              entries.skip_current_dir();
+            }
+
+            let (key, value) = to_action(
+              entry,
+              dir,
+              &self.settings.destination,
+              special_folders,
+              &set,
+            );
             self.actions.insert(
-              files::change_root_dir(
-                entry.path(),
-                dir,
-                &self.settings.destination,
-                false,
-              ),
-              to_file_entry(entry, 0),
+              key,
+              FileEntry {
+                symlink_dir,
+                ..value
+              },
             );
           }
-          Some(Err(err)) => print!("ERROR: {}\r\n", err),
+          // a missing or unreadable folder does not stop the others
+          Some(Err(err)) => self.errors.push(err.into()),
         };
       }
     }
+    Ok(())
   }
 
-  pub fn exclude_files(&mut self) {
-    let set = RegexSet::new(self.settings.excludes.clone()).unwrap();
-    self
-      .actions
-      .retain(|_k, v| !set.is_match(v.path.to_str().unwrap()));
+  pub fn exclude_files(&mut self) -> Result<(), ParoError> {
+    let settings = &self.settings;
+    let excludes = settings
+      .directories
+      .iter()
+      .map(|dir| Ok((dir, Patterns::new(&settings.excludes_for(dir))?)))
+      .collect::<Result<BTreeMap<_, _>, ParoError>>()?;
+    self.actions.retain(|_k, v| {
+      match relative_path(&v.path, &settings.directories) {
+        Some((dir, relative)) => {
+          !excludes[dir].is_match(relative, v.file_type.is_dir())
+        }
+        None => true,
+      }
+    });
+    Ok(())
   }
 
-  pub fn include_files(&mut self) {
-    for file in &self.settings.includes {
-      WalkDir::new(&file).into_iter().for_each(|e| match e {
-        Ok(entry) => {
-          let mut path = PathBuf::from(file);
-          path.pop();
-          self.actions.insert(
-            files::change_root_dir(
-              entry.path(),
-              &path.to_str().unwrap_or("").to_owned(),
+  // includes bring back hidden or excluded files along with their folders
+  pub fn include_files(&mut self) -> Result<(), ParoError> {
+    let special_folders = &self.settings.special_folder_vec();
+    let set = RegexSet::new(special_folders)?;
+
+    for dir in &self.settings.directories {
+      let includes = Patterns::new(&self.settings.includes_for(dir))?;
+      if includes.is_empty() {
+        continue;
+      }
+      let mut parents: Vec<DirEntry> = Vec::new();
+      let mut entries = WalkDir::new(dir).min_depth(1).into_iter();
+      loop {
+        let entry = match entries.next() {
+          None => break,
+          Some(Ok(entry)) => entry,
+          // already reported by select_files walking the same folders
+          Some(Err(_)) => continue,
+        };
+        parents.truncate(entry.depth() - 1);
+        let is_dir = entry.file_type().is_dir();
+        if entry.depth() == 1 && is_dir && entry.file_name() == hooks::HOOKS_DIR
+        {
+          entries.skip_current_dir();
+          continue;
+        }
+
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        if includes.is_match(relative, is_dir) {
+          for parent in parents.iter().chain([&entry]) {
+            let (key, value) = to_action(
+              parent.clone(),
+              dir,
               &self.settings.destination,
-              false,
-            ),
-            to_file_entry(entry, 0),
-          );
+              special_folders,
+              &set,
+            );
+            self.actions.entry(key).or_insert(value);
+          }
         }
-        Err(err) => print!("ERROR: {}\r\n", err),
-      });
+
+        let key = to_action(
+          entry.clone(),
+          dir,
+          &self.settings.destination,
+          special_folders,
+          &set,
+        )
+        .0;
+        if self
+          .actions
+          .get(&key)
+          .map(|v| v.symlink_dir)
+          .unwrap_or(false)
+        {
+          entries.skip_current_dir();
+        } else if is_dir {
+          parents.push(entry);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  pub fn copy_files(&mut self) -> Result<(), ParoError> {
+    let copy = self.settings.copy;
+    let set = RegexSet::new(&self.settings.copy_includes)?;
+    for value in self.actions.values_mut() {
+      if value.symlink_dir {
+        continue;
+      }
+      if copy || set.is_match(value.path.to_str().unwrap()) {
+        value.method = Method::Copy;
+      }
     }
+    Ok(())
   }
 
-  pub fn cleanup_special_folders(&mut self) {
+  pub fn cleanup_special_folders(&mut self) -> Result<(), ParoError> {
     let dir = self.settings.destination.clone();
-    let set =
-      RegexSet::new(vec![dir.clone() + "/tag-", dir + "/host-"]).unwrap();
+    let set = RegexSet::new(vec![dir.clone() + "/tag-", dir + "/host-"])?;
     self
       .actions
       .retain(|k, _v| !set.is_match(k.to_str().unwrap()));
+    Ok(())
   }
 
-  pub fn hide_files(&mut self) {
+  pub fn hide_files(&mut self) -> Result<(), ParoError> {
+    let set = RegexSet::new(&self.settings.no_hide)?;
+    let no_hide: Vec<PathBuf> = self
+      .actions
+      .iter()
+      .filter(|(_k, v)| v.depth == 1 && set.is_match(v.path.to_str().unwrap()))
+      .map(|(k, _v)| k.clone())
+      .collect();
+
+    // included files may sit in hidden folders, the top one decides
+    let destination = Path::new(&self.settings.destination);
     let mut new_actions = Actions::new();
     for (key, value) in self.actions.clone() {
+      let top = key
+        .strip_prefix(destination)
+        .ok()
+        .and_then(|path| path.iter().next())
+        .unwrap_or(key.as_os_str());
       if value.depth > 0
-        && !files::is_hidden(key.file_name().unwrap_or(key.as_os_str()))
+        && !files::is_hidden(top)
+        && !no_hide.iter().any(|path| key.starts_with(path))
       {
         new_actions.insert(
           files::change_root_dir(
@@ -157,29 +330,124 @@ impl FileActions {
         new_actions.insert(key, value);
       }
     }
-    self.actions = new_actions
+    self.actions = new_actions;
+    Ok(())
   }
 
-  pub fn build(&mut self) -> Self {
-    self.select_files();
-    self.exclude_files();
-    self.include_files();
-    self.cleanup_special_folders();
-    self.hide_files();
-    self.to_owned()
+  pub fn stale_links(&self) -> Result<Vec<PathBuf>, ParoError> {
+    let actions = &self.actions;
+    if self.settings.from_state {
+      return Ok(
+        Manifest::read_required(&self.settings.state_dir)?.stale_links(actions),
+      );
+    }
+
+    let directories: Vec<PathBuf> = self
+      .settings
+      .directories
+      .iter()
+      .filter_map(|dir| fs::canonicalize(dir).ok())
+      .collect();
+
+    // only the folders mirroring the dotfiles are walked, not all of $HOME
+    let mirrored = self.mirrored_dirs()?;
+    let mut stale = Vec::new();
+    let walker = WalkDir::new(&self.settings.destination)
+      .min_depth(1)
+      .into_iter()
+      .filter_entry(|e| !e.file_type().is_dir() || mirrored.contains(e.path()));
+    for entry in walker {
+      let entry = entry?;
+      if entry.path_is_symlink()
+        && !actions.contains_key(entry.path())
+        && files::is_link_into(entry.path(), &directories)
+      {
+        stale.push(entry.path().to_path_buf());
+      }
+    }
+    Ok(stale)
+  }
+
+  // the destination folders of every dotfiles folder, excluded ones too, and
+  // the folders of the state file for those removed from the dotfiles since
+  fn mirrored_dirs(&self) -> Result<BTreeSet<PathBuf>, ParoError> {
+    let destination = Path::new(&self.settings.destination);
+    let mut mirrored = BTreeSet::new();
+    for dir in &self.settings.directories {
+      for entry in WalkDir::new(dir).min_depth(1) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+          continue;
+        }
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        let mut components = relative.iter();
+        let top = components.next().unwrap_or_default().to_string_lossy();
+        // tag- and host- folders of any tag or host install at the top
+        let relative: PathBuf =
+          if top.starts_with("tag-") || top.starts_with("host-") {
+            components.collect()
+          } else {
+            relative.to_path_buf()
+          };
+        if relative.as_os_str().is_empty() {
+          continue;
+        }
+        mirrored.insert(destination.join(&relative));
+        mirrored.insert(files::change_root_dir(
+          &destination.join(&relative),
+          &self.settings.destination,
+          &self.settings.destination,
+          true,
+        ));
+      }
+    }
+
+    if let Some(manifest) = Manifest::read(&self.settings.state_dir)? {
+      for entry in manifest.entries {
+        mirrored.extend(
+          entry
+            .destination
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| {
+              dir.starts_with(destination) && *dir != destination
+            })
+            .map(Path::to_path_buf),
+        );
+      }
+    }
+    Ok(mirrored)
+  }
+
+  pub fn build(&mut self) -> Result<Self, ParoError> {
+    // down from the state file removes what was installed, whatever the settings
+    if self.settings.from_state && self.settings.operation == Operation::Down {
+      self.actions =
+        Manifest::read_required(&self.settings.state_dir)?.to_actions();
+      return Ok(self.to_owned());
+    }
+
+    self.select_files()?;
+    self.exclude_files()?;
+    self.include_files()?;
+    self.copy_files()?;
+    self.cleanup_special_folders()?;
+    self.hide_files()?;
+    Ok(self.to_owned())
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::manifest::ManifestEntry;
+  use crate::patterns::IGNORE_FILE;
 
   fn to_str_dest_files(files: FileActions) -> Vec<String> {
     let mut str_dest_files: Vec<String> = files
       .actions
-      .clone()
-      .into_iter()
-      .map(|(k, _v)| k.to_str().unwrap().to_string())
+      .keys()
+      .map(|k| k.to_str().unwrap().to_string())
       .collect();
     str_dest_files.sort();
     str_dest_files
@@ -187,20 +455,24 @@ mod tests {
 
   #[test]
   fn test_select_files() {
-    let mut settings = Settings::default();
-    settings.directories = vec!["tests/example-dotfiles/".to_string()];
-    settings.destination = "/destiny".to_string();
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles/".to_string()],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
     let mut files: FileActions = FileActions::new(settings);
 
-    files.select_files();
+    files.select_files().unwrap();
 
     let str_dest_files: Vec<String> = to_str_dest_files(files);
 
-    assert_eq!(str_dest_files.len(), 14);
+    assert_eq!(str_dest_files.len(), 16);
     assert_eq!(
       str_dest_files,
       vec![
         "/destiny/",
+        "/destiny/bin",
+        "/destiny/bin/paro-script",
         "/destiny/folder",
         "/destiny/folder/something.txt",
         "/destiny/host-dois",
@@ -218,23 +490,130 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_select_files_missing_dir() {
+    let settings = Settings {
+      directories: vec![
+        "tests/missing-dotfiles".to_string(),
+        "tests/example-hooks".to_string(),
+      ],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    files.select_files().unwrap();
+
+    assert_eq!(files.errors.len(), 1);
+    assert_eq!(files.errors[0].exit_code(), 1);
+    assert_eq!(
+      to_str_dest_files(files),
+      vec!["/destiny/", "/destiny/hook-file.txt"]
+    );
+  }
+
+  #[test]
+  fn test_select_files_without_hooks() {
+    let settings = Settings {
+      directories: vec!["tests/example-hooks".to_string()],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    files.select_files().unwrap();
+
+    assert_eq!(
+      to_str_dest_files(files),
+      vec!["/destiny/", "/destiny/hook-file.txt"]
+    );
+  }
+
+  #[test]
+  fn test_select_files_paroignore() {
+    let dir = Path::new("tests/paroignore-dotfiles");
+    let _ = fs::remove_dir_all(dir);
+    for folder in ["docs", "scripts"] {
+      fs::create_dir_all(dir.join(folder)).unwrap();
+    }
+    for file in [
+      "README.md",
+      "LICENSE",
+      "vimrc",
+      "docs/notes.md",
+      "docs/todo.md",
+      "scripts/install.sh",
+    ] {
+      fs::write(dir.join(file), "").unwrap();
+    }
+    fs::write(
+      dir.join(IGNORE_FILE),
+      "# shipped with the repo\nREADME.md\nLICENSE\n*.md\n!docs/notes.md\n\
+       scripts/\n!scripts/install.sh\n",
+    )
+    .unwrap();
+    let settings = Settings {
+      directories: vec!["tests/paroignore-dotfiles".to_string()],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    let selected = files.select_files();
+    fs::remove_dir_all(dir).unwrap();
+    selected.unwrap();
+
+    assert_eq!(
+      to_str_dest_files(files),
+      vec![
+        "/destiny/",
+        "/destiny/docs",
+        "/destiny/docs/notes.md",
+        "/destiny/vimrc"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_select_files_symlink_dirs() {
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles/".to_string()],
+      destination: "/destiny".to_string(),
+      symlink_dirs: vec!["example-dotfiles/folder$".to_string()],
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    files.select_files().unwrap();
+
+    assert!(files.actions[&PathBuf::from("/destiny/folder")].symlink_dir);
+    assert!(!files.actions[&PathBuf::from("/destiny/bin")].symlink_dir);
+    assert!(!files
+      .actions
+      .contains_key(&PathBuf::from("/destiny/folder/something.txt")));
+  }
+
   #[test]
   fn test_hide_files() {
-    let mut settings = Settings::default();
-    settings.directories = vec!["tests/example-dotfiles/".to_string()];
-    settings.destination = "/destiny".to_string();
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles/".to_string()],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
     let mut files: FileActions = FileActions::new(settings);
 
-    files.select_files();
-    files.hide_files();
+    files.select_files().unwrap();
+    files.hide_files().unwrap();
 
     let str_dest_files: Vec<String> = to_str_dest_files(files);
 
-    assert_eq!(str_dest_files.len(), 14);
+    assert_eq!(str_dest_files.len(), 16);
     assert_eq!(
       str_dest_files,
       vec![
         "/destiny/",
+        "/destiny/.bin",
+        "/destiny/.bin/paro-script",
         "/destiny/.folder",
         "/destiny/.folder/something.txt",
         "/destiny/.host-dois",
@@ -252,17 +631,46 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_hide_files_no_hide() {
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles/".to_string()],
+      destination: "/destiny".to_string(),
+      no_hide: vec!["example-dotfiles/bin$".to_string()],
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    files.select_files().unwrap();
+    files.cleanup_special_folders().unwrap();
+    files.hide_files().unwrap();
+
+    assert_eq!(
+      to_str_dest_files(files),
+      vec![
+        "/destiny/",
+        "/destiny/.folder",
+        "/destiny/.folder/something.txt",
+        "/destiny/.normal-file.txt",
+        "/destiny/bin",
+        "/destiny/bin/paro-script",
+      ]
+    );
+  }
+
   #[test]
   fn test_select_files_with_tag_host() {
-    let mut settings = Settings::default();
-    settings.directories = vec!["tests/example-dotfiles/".to_string()];
-    settings.destination = "/destiny".to_string();
-    settings.tags = vec!["um".to_string()];
-    settings.hostname = "dois".to_string();
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles/".to_string()],
+      destination: "/destiny".to_string(),
+      tags: vec!["um".to_string()],
+      hostname: "dois".to_string(),
+      ..Default::default()
+    };
     let mut files: FileActions = FileActions::new(settings);
 
-    files.select_files();
-    files.cleanup_special_folders();
+    files.select_files().unwrap();
+    files.cleanup_special_folders().unwrap();
 
     let mut str_dest_files: Vec<String> = files
       .actions
@@ -274,11 +682,13 @@ mod tests {
       .collect();
     str_dest_files.sort();
 
-    assert_eq!(str_dest_files.len(), 6);
+    assert_eq!(str_dest_files.len(), 8);
     assert_eq!(
       str_dest_files,
       vec![
         "/destiny/:0",
+        "/destiny/bin/paro-script:2",
+        "/destiny/bin:1",
         "/destiny/file.txt:1",
         "/destiny/file1.txt:1",
         "/destiny/folder/something.txt:2",
@@ -290,58 +700,221 @@ mod tests {
 
   #[test]
   fn test_exclude_files() {
-    let mut settings = Settings::default();
-    settings.directories = vec![
-      "tests/example-dotfiles/folder".to_string(),
-      "tests/example-dotfiles/tag-um".to_string(),
-    ];
-    settings.excludes = vec![
-      "tests/example-dotfiles/folder*".to_string(),
-      "tests/example-dotfiles/tag-um/.file.txt".to_string(),
-      "tests/example-dotfiles/tag-um/.file1.txt".to_string(),
-    ];
-    settings.destination = "/destiny".to_string();
+    let settings = Settings {
+      directories: vec![
+        "tests/example-dotfiles/folder".to_string(),
+        "tests/example-dotfiles/tag-um".to_string(),
+      ],
+      excludes: vec!["some*".to_string(), "/file1.txt".to_string()],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
     let mut files: FileActions = FileActions::new(settings);
 
-    files.select_files();
-    files.exclude_files();
+    files.select_files().unwrap();
+    files.exclude_files().unwrap();
 
     let str_dest_files: Vec<String> = to_str_dest_files(files);
 
-    assert_eq!(str_dest_files.len(), 3);
+    assert_eq!(str_dest_files.len(), 2);
+    assert_eq!(str_dest_files, vec!["/destiny/", "/destiny/file.txt"]);
+  }
+
+  #[test]
+  fn test_exclude_files_relative() {
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles".to_string()],
+      excludes: vec![
+        "file.txt".to_string(),
+        "bin/".to_string(),
+        "re:^folder".to_string(),
+      ],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    files.select_files().unwrap();
+    files.exclude_files().unwrap();
+
+    // the dotfiles path does not count and file.txt is not normal-file.txt
     assert_eq!(
-      str_dest_files,
-      vec!["/destiny/", "/destiny/file.txt", "/destiny/file1.txt"]
+      to_str_dest_files(files),
+      vec![
+        "/destiny/",
+        "/destiny/host-dois",
+        "/destiny/host-um",
+        "/destiny/normal-file.txt",
+        "/destiny/tag-dois",
+        "/destiny/tag-dois/file2.txt",
+        "/destiny/tag-um",
+        "/destiny/tag-um/file1.txt",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_exclude_files_scoped() {
+    let settings = Settings {
+      directories: vec![
+        "tests/example-dotfiles".to_string(),
+        "tests/example-hooks".to_string(),
+      ],
+      excludes: vec![
+        "example-hooks:*.txt".to_string(),
+        "tests/example-dotfiles:/*".to_string(),
+      ],
+      includes: vec!["example-dotfiles:/normal-file.txt".to_string()],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    files.select_files().unwrap();
+    files.exclude_files().unwrap();
+    files.include_files().unwrap();
+
+    // each folder only gets the patterns scoped to it
+    assert_eq!(
+      to_str_dest_files(files),
+      vec!["/destiny/", "/destiny/normal-file.txt"]
     );
   }
 
   #[test]
   fn test_include_files() {
-    let mut settings = Settings::default();
-    settings.directories = vec![
-      "tests/example-dotfiles/folder".to_string(),
-      "tests/example-dotfiles/tag-um".to_string(),
-    ];
-    settings.includes =
-      vec!["tests/example-dotfiles/.ignored-file".to_string()];
-    settings.destination = "/destiny".to_string();
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles".to_string()],
+      excludes: vec!["*".to_string()],
+      includes: vec![
+        ".ignored-file".to_string(),
+        ".ignored-folder/*.txt".to_string(),
+        "bin/paro-script".to_string(),
+        "tag-um/.file1.txt".to_string(),
+      ],
+      tags: vec!["um".to_string()],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
     let mut files: FileActions = FileActions::new(settings);
 
-    files.select_files();
-    files.include_files();
-    files.hide_files();
+    files.select_files().unwrap();
+    files.exclude_files().unwrap();
+    files.include_files().unwrap();
+    files.cleanup_special_folders().unwrap();
+    files.hide_files().unwrap();
 
     let str_dest_files: Vec<String> = to_str_dest_files(files);
 
-    assert_eq!(str_dest_files.len(), 5);
+    // hidden and excluded files come back along with their folders
+    assert_eq!(str_dest_files.len(), 7);
     assert_eq!(
       str_dest_files,
       vec![
         "/destiny/",
-        "/destiny/.file.txt",
+        "/destiny/.bin",
+        "/destiny/.bin/paro-script",
         "/destiny/.file1.txt",
         "/destiny/.ignored-file",
-        "/destiny/.something.txt",
+        "/destiny/.ignored-folder",
+        "/destiny/.ignored-folder/file.txt",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_copy_files() {
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles/folder".to_string()],
+      copy_includes: vec!["something.txt$".to_string()],
+      destination: "/destiny".to_string(),
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    files.select_files().unwrap();
+    files.copy_files().unwrap();
+
+    let mut str_dest_files: Vec<String> = files
+      .actions
+      .iter()
+      .map(|(k, v)| {
+        k.to_str().unwrap().to_string() + ":" + &v.method.to_string()
+      })
+      .collect();
+    str_dest_files.sort();
+
+    assert_eq!(
+      str_dest_files,
+      vec!["/destiny/:link", "/destiny/something.txt:copy"]
+    );
+
+    let settings = Settings {
+      directories: vec!["tests/example-dotfiles/folder".to_string()],
+      destination: "/destiny".to_string(),
+      copy: true,
+      ..Default::default()
+    };
+    let mut files: FileActions = FileActions::new(settings);
+
+    files.select_files().unwrap();
+    files.copy_files().unwrap();
+
+    assert!(files.actions.values().all(|v| v.method == Method::Copy));
+  }
+
+  #[test]
+  fn test_stale_links() {
+    let home = Path::new("tests/stale-destination");
+    let state = "tests/stale-state";
+    let _ = fs::remove_dir_all(home);
+    let _ = fs::remove_dir_all(state);
+    for folder in [".folder", ".old", "cache"] {
+      fs::create_dir_all(home.join(folder)).unwrap();
+    }
+    let dotfiles = fs::canonicalize("tests/example-dotfiles").unwrap();
+    let link = |target: PathBuf, path: &str| {
+      std::os::unix::fs::symlink(target, home.join(path)).unwrap()
+    };
+    link(
+      dotfiles.join("folder/something.txt"),
+      ".folder/something.txt",
+    );
+    link(dotfiles.join("normal-file.txt"), ".normal-file.txt");
+    link(dotfiles.join("old/file.txt"), ".old/file.txt");
+    link(dotfiles.join("normal-file.txt"), "cache/normal-file.txt");
+    // a folder removed from the dotfiles is only known from the state file
+    Manifest {
+      entries: vec![ManifestEntry {
+        destination: home.join(".old/file.txt"),
+        source: dotfiles.join("old/file.txt"),
+        method: Method::Link,
+        symlink_dir: false,
+        special_folder: None,
+      }],
+      ..Default::default()
+    }
+    .write(state)
+    .unwrap();
+
+    let stale = FileActions::new(Settings {
+      directories: vec!["tests/example-dotfiles".to_string()],
+      destination: "tests/stale-destination".to_string(),
+      state_dir: state.to_string(),
+      excludes: vec!["folder".to_string()],
+      ..Default::default()
+    })
+    .build()
+    .and_then(|file_actions| file_actions.stale_links());
+    fs::remove_dir_all(home).unwrap();
+    fs::remove_dir_all(state).unwrap();
+
+    // folders that mirror nothing in the dotfiles are not walked
+    assert_eq!(
+      stale.unwrap(),
+      [
+        home.join(".folder/something.txt"),
+        home.join(".old/file.txt")
       ]
     );
   }
@@ -361,6 +934,9 @@ mod tests {
         path: file_one.path().to_path_buf(),
         file_type: file_one.file_type(),
         depth: 2,
+        method: Method::Link,
+        symlink_dir: false,
+        special_folder: None,
       }
     );
 
@@ -370,6 +946,9 @@ mod tests {
         path: file_two.path().to_path_buf(),
         file_type: file_two.file_type(),
         depth: 1,
+        method: Method::Link,
+        symlink_dir: false,
+        special_folder: None,
       }
     );
   }
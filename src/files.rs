@@ -1,8 +1,9 @@
+use crate::error::ParoError;
 use std::ffi::OsStr;
 use std::fs;
 use std::io::{self, Error, ErrorKind};
 use std::os::unix::fs::{symlink, MetadataExt};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 pub fn canonicalize_path(entry: String) -> io::Result<String> {
   if entry.is_empty() {
@@ -12,7 +13,7 @@ pub fn canonicalize_path(entry: String) -> io::Result<String> {
   match fs::canonicalize(&entry) {
     Ok(result) => Ok(result.to_str().unwrap_or("").to_owned()),
     Err(err) => Err(Error::new(
-      ErrorKind::InvalidData,
+      err.kind(),
       format!("Error {}: Invalid path {:?}.", err.kind(), entry),
     )),
   }
@@ -36,18 +37,59 @@ pub fn change_root_dir(
   }
 }
 
+pub fn revert_root_dir(
+  destiny_path: &Path,
+  current: &Path,
+  new: &Path,
+) -> Option<PathBuf> {
+  let mut components = destiny_path.strip_prefix(current).ok()?.components();
+  let first = components.next()?.as_os_str().to_string_lossy().to_string();
+  let name = first.strip_prefix('.').unwrap_or(&first);
+  if name.is_empty() {
+    return None;
+  }
+  let mut path = new.join(name);
+  if components.clone().next().is_some() {
+    path.push(components.as_path());
+  }
+  Some(path)
+}
+
 pub fn is_hidden(entry: &OsStr) -> bool {
   entry.to_str().map(|s| s.starts_with('.')).unwrap_or(false)
 }
 
+pub fn has_same_content(
+  origin_file: &Path,
+  destiny_file: &Path,
+) -> Result<bool, std::io::Error> {
+  if !origin_file.is_file()
+    || !destiny_file.is_file()
+    || destiny_file.is_symlink()
+  {
+    return Ok(false);
+  }
+
+  if origin_file.metadata()?.len() != destiny_file.metadata()?.len() {
+    return Ok(false);
+  }
+
+  Ok(fs::read(origin_file)? == fs::read(destiny_file)?)
+}
+
 pub fn is_same_file(
   origin_file: &Path,
   destiny_file: &Path,
+  copy: bool,
 ) -> Result<bool, std::io::Error> {
   if !origin_file.exists() || !destiny_file.exists() {
     return Ok(false);
   }
 
+  if copy {
+    return has_same_content(origin_file, destiny_file);
+  }
+
   let m1 = origin_file.metadata()?;
   let m2 = destiny_file.metadata()?;
 
@@ -62,40 +104,98 @@ pub fn is_same_file(
   Ok(true)
 }
 
-pub fn force_delete_file(destiny_file: &Path) {
-  if let Err(err) = fs::remove_file(destiny_file) {
-    if err.kind() != std::io::ErrorKind::NotFound {
-      print!("ERROR: {} {:?} {}\r\n", err, destiny_file, err.kind());
-    }
-  }
+pub fn is_present(destiny_file: &Path) -> bool {
+  destiny_file.symlink_metadata().is_ok()
+}
+
+pub fn read_link(destiny_file: &Path) -> PathBuf {
+  fs::read_link(destiny_file).unwrap_or_default()
+}
+
+pub fn normalize_path(path: &Path) -> PathBuf {
+  path
+    .components()
+    .fold(PathBuf::new(), |mut normalized, component| {
+      match component {
+        Component::ParentDir => {
+          normalized.pop();
+        }
+        Component::CurDir => {}
+        _ => normalized.push(component),
+      };
+      normalized
+    })
 }
 
-pub fn delete_file(destiny_file: &Path) {
-  if let Err(err) = fs::remove_file(destiny_file) {
-    print!("ERROR: {} {:?}\r\n", err, destiny_file)
+pub fn is_link_into(destiny_file: &Path, directories: &[PathBuf]) -> bool {
+  match fs::read_link(destiny_file) {
+    Ok(target) => {
+      let target = match destiny_file.parent() {
+        Some(parent) if target.is_relative() => parent.join(target),
+        _ => target,
+      };
+      let target = normalize_path(&target);
+      directories.iter().any(|dir| target.starts_with(dir))
+    }
+    Err(_) => false,
   }
 }
 
-pub fn create_symlink(origin_file: &Path, destiny_file: &Path) {
-  match fs::canonicalize(origin_file) {
-    Err(err) => print!("ERROR: {} {:?}\r\n", err, origin_file),
-    Ok(result) => {
-      if let Err(err) = symlink(result, destiny_file) {
-        print!("ERROR: {} {:?}\r\n", err, destiny_file)
-      }
+pub fn force_delete_file(destiny_file: &Path) -> Result<(), ParoError> {
+  match fs::remove_file(destiny_file) {
+    Err(err) if err.kind() != ErrorKind::NotFound => {
+      Err(ParoError::io(err, destiny_file))
     }
+    _ => Ok(()),
   }
 }
 
-pub fn overwrite_symlink(origin_file: &Path, destiny_file: &Path) {
-  force_delete_file(destiny_file);
-  create_symlink(origin_file, destiny_file);
+pub fn delete_file(destiny_file: &Path) -> Result<(), ParoError> {
+  fs::remove_file(destiny_file).map_err(|err| ParoError::io(err, destiny_file))
+}
+
+pub fn create_symlink(
+  origin_file: &Path,
+  destiny_file: &Path,
+) -> Result<(), ParoError> {
+  let origin = fs::canonicalize(origin_file)
+    .map_err(|err| ParoError::io(err, origin_file))?;
+  symlink(origin, destiny_file).map_err(|err| ParoError::io(err, destiny_file))
+}
+
+pub fn create_copy(
+  origin_file: &Path,
+  destiny_file: &Path,
+) -> Result<(), ParoError> {
+  fs::copy(origin_file, destiny_file)
+    .map(|_| ())
+    .map_err(|err| ParoError::io(err, destiny_file))
 }
 
-pub fn create_dir(destiny_file: &Path) {
-  if let Err(err) = fs::create_dir_all(destiny_file) {
-    print!("ERROR: {} {:?}\r\n", err, destiny_file)
+pub fn move_file(
+  origin_file: &Path,
+  destiny_file: &Path,
+) -> Result<(), ParoError> {
+  if let Some(parent) = destiny_file.parent() {
+    create_dir(parent)?;
   }
+  match fs::rename(origin_file, destiny_file) {
+    Ok(()) => Ok(()),
+    // folders are only moved within the same file system
+    Err(err) if origin_file.is_dir() && !origin_file.is_symlink() => {
+      Err(ParoError::io(err, origin_file))
+    }
+    // rename does not work across file systems, fallback to copy and delete
+    Err(_) => {
+      create_copy(origin_file, destiny_file)?;
+      delete_file(origin_file)
+    }
+  }
+}
+
+pub fn create_dir(destiny_file: &Path) -> Result<(), ParoError> {
+  fs::create_dir_all(destiny_file)
+    .map_err(|err| ParoError::io(err, destiny_file))
 }
 
 #[cfg(test)]
@@ -109,22 +209,79 @@ mod tests {
     let path_1 = Path::new("tests/example-dotfiles/tag-um/file.txt");
     let path_2 = Path::new("tests/example-dotfiles/tag-um/file.txt");
 
-    assert_eq!(is_same_file(path_1, path_2).unwrap(), true);
+    assert!(is_same_file(path_1, path_2, false).unwrap());
 
     let path_1 = Path::new("tests/example-dotfiles/tag-um/file.txt");
     let path_2 = Path::new("tests/example-dotfiles/tag-um/.file.txt");
 
-    assert_eq!(is_same_file(path_1, path_2).unwrap(), false);
+    assert!(!is_same_file(path_1, path_2, false).unwrap());
 
     let path_1 = Path::new("non-existing.txt");
     let path_2 = Path::new("tests/example-dotfiles/tag-um/file.txt");
 
-    assert_eq!(is_same_file(path_1, path_2).unwrap(), false);
+    assert!(!is_same_file(path_1, path_2, false).unwrap());
 
     let path_1 = Path::new("tests/example-dotfiles/tag-um/file.txt");
     let path_2 = Path::new("non-existing.txt");
 
-    assert_eq!(is_same_file(path_1, path_2).unwrap(), false);
+    assert!(!is_same_file(path_1, path_2, false).unwrap());
+  }
+
+  #[test]
+  fn test_is_same_file_copy() {
+    let path_1 = Path::new("tests/example-dotfiles/tag-um/file.txt");
+    let path_2 = Path::new("tests/example-dotfiles/tag-um/file1.txt");
+
+    // empty files share the same content but not the same inode
+    assert!(is_same_file(path_1, path_2, true).unwrap());
+    assert!(!is_same_file(path_1, path_2, false).unwrap());
+
+    let path_1 = Path::new("tests/settings.toml");
+    let path_2 = Path::new("tests/example-dotfiles/tag-um/file.txt");
+
+    assert!(!is_same_file(path_1, path_2, true).unwrap());
+
+    let path_1 = Path::new("tests/example-dotfiles/tag-um");
+    let path_2 = Path::new("tests/example-dotfiles/tag-um");
+
+    assert!(!is_same_file(path_1, path_2, true).unwrap());
+  }
+
+  #[test]
+  fn test_is_link_into() {
+    let dir = PathBuf::from("tests/link-into");
+    let _ = fs::remove_dir_all(&dir);
+    create_dir(&dir).unwrap();
+    let link = fs::canonicalize(&dir).unwrap().join("link");
+    symlink("../example-dotfiles/normal-file.txt", &link).unwrap();
+    let dotfiles = vec![fs::canonicalize("tests/example-dotfiles").unwrap()];
+
+    assert!(is_link_into(&link, &dotfiles));
+    assert!(!is_link_into(&link, &[dotfiles[0].join("folder")]));
+    assert!(!is_link_into(
+      &dotfiles[0].join("normal-file.txt"),
+      &dotfiles
+    ));
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_is_present() {
+    assert!(is_present(Path::new(
+      "tests/example-dotfiles/normal-file.txt"
+    )));
+    assert!(!is_present(Path::new(
+      "tests/example-dotfiles/non-existing"
+    )));
+  }
+
+  #[test]
+  fn test_normalize_path() {
+    assert_eq!(
+      normalize_path(Path::new("/test/./dir/../file.txt")),
+      PathBuf::from("/test/file.txt")
+    );
   }
 
   #[test]
@@ -159,16 +316,63 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_revert_root_dir() {
+    // should change root and remove the leading dot
+    assert_eq!(
+      revert_root_dir(
+        Path::new("/home/.config/nvim/init.vim"),
+        Path::new("/home"),
+        Path::new("/dotfiles"),
+      ),
+      Some(PathBuf::from("/dotfiles/config/nvim/init.vim"))
+    );
+
+    assert_eq!(
+      revert_root_dir(
+        Path::new("/home/.vimrc"),
+        Path::new("/home"),
+        Path::new("/dotfiles"),
+      ),
+      Some(PathBuf::from("/dotfiles/vimrc"))
+    );
+
+    // should keep not hidden files
+    assert_eq!(
+      revert_root_dir(
+        Path::new("/home/bin/script"),
+        Path::new("/home/"),
+        Path::new("/dotfiles/tag-work"),
+      ),
+      Some(PathBuf::from("/dotfiles/tag-work/bin/script"))
+    );
+
+    // should ignore paths outside of the current root
+    assert_eq!(
+      revert_root_dir(
+        Path::new("/other/.vimrc"),
+        Path::new("/home"),
+        Path::new("/dotfiles"),
+      ),
+      None
+    );
+    assert_eq!(
+      revert_root_dir(
+        Path::new("/home"),
+        Path::new("/home"),
+        Path::new("/dotfiles"),
+      ),
+      None
+    );
+  }
+
   #[test]
   fn test_is_hidden() {
     let mut files = WalkDir::new("tests/example-dotfiles/tag-um/")
       .sort_by_file_name()
       .into_iter();
-    assert_eq!(
-      is_hidden(&files.next().unwrap().unwrap().file_name()),
-      false
-    );
-    assert_eq!(is_hidden(&files.next().unwrap().unwrap().file_name()), true);
+    assert!(!is_hidden(files.next().unwrap().unwrap().file_name()));
+    assert!(is_hidden(files.next().unwrap().unwrap().file_name()));
   }
 
   #[test]
@@ -0,0 +1,167 @@
+use crate::settings::Settings;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+pub const HOOKS_DIR: &str = "hooks";
+
+#[derive(Debug, Clone, Copy)]
+pub enum Hook {
+  PreUp,
+  PostUp,
+  PreDown,
+  PostDown,
+}
+
+impl fmt::Display for Hook {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Hook::PreUp => write!(f, "pre-up"),
+      Hook::PostUp => write!(f, "post-up"),
+      Hook::PreDown => write!(f, "pre-down"),
+      Hook::PostDown => write!(f, "post-down"),
+    }
+  }
+}
+
+pub fn is_executable(path: &Path) -> bool {
+  path.is_file()
+    && path
+      .metadata()
+      .map(|m| m.permissions().mode() & 0o111 != 0)
+      .unwrap_or(false)
+}
+
+pub fn hook_files(dir: &str, hook: Hook) -> Vec<PathBuf> {
+  let path = Path::new(dir).join(HOOKS_DIR).join(hook.to_string());
+
+  if !path.is_dir() {
+    return if is_executable(&path) {
+      vec![path]
+    } else {
+      vec![]
+    };
+  }
+
+  let mut hooks: Vec<PathBuf> = match fs::read_dir(&path) {
+    Ok(entries) => entries
+      .filter_map(|e| e.ok())
+      .map(|e| e.path())
+      .filter(|p| is_executable(p))
+      .collect(),
+    Err(_) => vec![],
+  };
+  hooks.sort();
+  hooks
+}
+
+pub fn hook_env(settings: &Settings, hook: Hook) -> Vec<(String, String)> {
+  vec![
+    ("PARO_HOOK".to_string(), hook.to_string()),
+    ("PARO_DESTINATION".to_string(), settings.destination.clone()),
+    ("PARO_HOSTNAME".to_string(), settings.hostname.clone()),
+    ("PARO_TAGS".to_string(), settings.tags.join(" ")),
+    (
+      "PARO_DIRECTORIES".to_string(),
+      settings.directories.join(":"),
+    ),
+    ("PARO_DRY_RUN".to_string(), settings.dry_run.to_string()),
+  ]
+}
+
+pub fn run_hook(
+  file: &Path,
+  dir: &str,
+  env: &[(String, String)],
+) -> io::Result<Output> {
+  Command::new(fs::canonicalize(file)?)
+    .current_dir(dir)
+    .envs(env.iter().map(|(k, v)| (k, v)))
+    .output()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_hook_files() {
+    assert_eq!(
+      hook_files("tests/example-hooks", Hook::PreUp),
+      vec![
+        PathBuf::from("tests/example-hooks/hooks/pre-up/01-first"),
+        PathBuf::from("tests/example-hooks/hooks/pre-up/02-second"),
+      ]
+    );
+    assert_eq!(
+      hook_files("tests/example-hooks", Hook::PreDown),
+      vec![PathBuf::from("tests/example-hooks/hooks/pre-down")]
+    );
+    assert_eq!(
+      hook_files("tests/example-hooks", Hook::PostDown),
+      Vec::<PathBuf>::new()
+    );
+  }
+
+  #[test]
+  fn test_run_hook() {
+    let output = run_hook(
+      Path::new("tests/example-hooks/hooks/pre-up/01-first"),
+      "tests/example-hooks",
+      &[],
+    )
+    .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+      String::from_utf8_lossy(&output.stdout),
+      "first pre-up hook\n"
+    );
+
+    let output = run_hook(
+      Path::new("tests/example-hooks/hooks/pre-down"),
+      "tests/example-hooks",
+      &[],
+    )
+    .unwrap();
+    assert!(!output.status.success());
+  }
+
+  #[test]
+  fn test_hook_env() {
+    let settings = Settings {
+      tags: vec!["um".to_string(), "dois".to_string()],
+      directories: vec!["home/".to_string(), "dome/".to_string()],
+      destination: "/destiny".to_string(),
+      hostname: "host".to_string(),
+      dry_run: true,
+      ..Default::default()
+    };
+    let env = hook_env(&settings, Hook::PostUp);
+
+    assert_eq!(
+      env,
+      vec![
+        ("PARO_HOOK".to_string(), "post-up".to_string()),
+        ("PARO_DESTINATION".to_string(), "/destiny".to_string()),
+        ("PARO_HOSTNAME".to_string(), "host".to_string()),
+        ("PARO_TAGS".to_string(), "um dois".to_string()),
+        ("PARO_DIRECTORIES".to_string(), "home/:dome/".to_string()),
+        ("PARO_DRY_RUN".to_string(), "true".to_string()),
+      ]
+    );
+
+    let output = run_hook(
+      Path::new("tests/example-hooks/hooks/post-up"),
+      "tests/example-hooks",
+      &env,
+    )
+    .unwrap();
+    assert_eq!(
+      String::from_utf8_lossy(&output.stdout),
+      "post-up um dois true\n"
+    );
+  }
+}
@@ -0,0 +1,349 @@
+use crate::backup;
+use crate::error::ParoError;
+use crate::files;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::symlink;
+use std::path::{self, Path, PathBuf};
+
+pub const JOURNAL_DIR: &str = "journal";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "kebab-case")]
+pub enum Change {
+  Mkdir {
+    path: PathBuf,
+  },
+  Link {
+    path: PathBuf,
+    origin: PathBuf,
+  },
+  Copy {
+    path: PathBuf,
+    origin: PathBuf,
+  },
+  Move {
+    path: PathBuf,
+    origin: PathBuf,
+  },
+  Delete {
+    path: PathBuf,
+    link: Option<PathBuf>,
+  },
+  Rmdir {
+    path: PathBuf,
+  },
+}
+
+impl fmt::Display for Change {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Change::Mkdir { path } => write!(f, "mkdir {:?}", path),
+      Change::Link { path, origin } => {
+        write!(f, "link {:?} -> {:?}", origin, path)
+      }
+      Change::Copy { path, origin } => {
+        write!(f, "copy {:?} -> {:?}", origin, path)
+      }
+      Change::Move { path, origin } => {
+        write!(f, "move {:?} -> {:?}", origin, path)
+      }
+      Change::Delete { path, .. } => write!(f, "delete {:?}", path),
+      Change::Rmdir { path } => write!(f, "rmdir {:?}", path),
+    }
+  }
+}
+
+impl Change {
+  // a replaced file is either moved into the backup folder or deleted
+  pub fn remove(path: &Path, backup: Option<PathBuf>) -> Self {
+    match backup {
+      Some(backup) => Change::Move {
+        path: backup,
+        origin: path.to_path_buf(),
+      },
+      None => Change::Delete {
+        path: path.to_path_buf(),
+        link: fs::read_link(path).ok(),
+      },
+    }
+  }
+
+  // undo may run from another folder, so only full paths are recorded and
+  // the links point to the canonical origin
+  pub fn absolute(&self) -> Self {
+    let absolute =
+      |path: &PathBuf| path::absolute(path).unwrap_or_else(|_| path.clone());
+    let canonical = |path: &PathBuf| {
+      fs::canonicalize(path).unwrap_or_else(|_| absolute(path))
+    };
+    match self {
+      Change::Mkdir { path } => Change::Mkdir {
+        path: absolute(path),
+      },
+      Change::Link { path, origin } => Change::Link {
+        path: absolute(path),
+        origin: canonical(origin),
+      },
+      Change::Copy { path, origin } => Change::Copy {
+        path: absolute(path),
+        origin: canonical(origin),
+      },
+      Change::Move { path, origin } => Change::Move {
+        path: absolute(path),
+        origin: absolute(origin),
+      },
+      Change::Delete { path, link } => Change::Delete {
+        path: absolute(path),
+        link: link.clone(),
+      },
+      Change::Rmdir { path } => Change::Rmdir {
+        path: absolute(path),
+      },
+    }
+  }
+
+  pub fn apply(&self) -> Result<(), ParoError> {
+    match self {
+      Change::Mkdir { path } => files::create_dir(path),
+      Change::Link { path, origin } => files::create_symlink(origin, path),
+      Change::Copy { path, origin } => files::create_copy(origin, path),
+      Change::Move { path, origin } => files::move_file(origin, path),
+      Change::Delete { path, .. } => files::force_delete_file(path),
+      Change::Rmdir { path } => {
+        fs::remove_dir(path).map_err(|err| ParoError::io(err, path))
+      }
+    }
+  }
+
+  // files changed after the run are left alone and reported as conflicts
+  pub fn undo(&self) -> Result<(), ParoError> {
+    match self {
+      Change::Mkdir { path } => {
+        fs::remove_dir(path).map_err(|err| ParoError::io(err, path))
+      }
+      Change::Link { path, origin } if links_to(path, origin) => {
+        files::delete_file(path)
+      }
+      Change::Copy { path, origin }
+        if files::has_same_content(origin, path).unwrap_or(false) =>
+      {
+        files::delete_file(path)
+      }
+      Change::Link { path, .. } | Change::Copy { path, .. } => {
+        if files::is_present(path) {
+          Err(ParoError::Conflict(path.clone()))
+        } else {
+          Ok(())
+        }
+      }
+      Change::Move { origin, .. } | Change::Delete { path: origin, .. }
+        if files::is_present(origin) =>
+      {
+        Err(ParoError::Conflict(origin.clone()))
+      }
+      Change::Move { path, origin } => files::move_file(path, origin),
+      Change::Delete {
+        path,
+        link: Some(link),
+      } => symlink(link, path).map_err(|err| ParoError::io(err, path)),
+      Change::Delete { path, link: None } => Err(ParoError::Config(format!(
+        "{:?} was deleted without a backup",
+        path
+      ))),
+      Change::Rmdir { path } => files::create_dir(path),
+    }
+  }
+}
+
+// apply links to the canonical origin, which may be gone by now
+fn links_to(path: &Path, origin: &Path) -> bool {
+  let target = files::read_link(path);
+  target == origin
+    || fs::canonicalize(origin)
+      .map(|origin| origin == target)
+      .unwrap_or(false)
+}
+
+pub fn journal_dir(state_dir: &str) -> PathBuf {
+  Path::new(state_dir).join(JOURNAL_DIR)
+}
+
+pub fn journal_file(state_dir: &str, name: &str) -> PathBuf {
+  journal_dir(state_dir).join(format!("{}.jsonl", name))
+}
+
+pub fn latest_journal(state_dir: &str) -> Option<PathBuf> {
+  fs::read_dir(journal_dir(state_dir))
+    .ok()?
+    .filter_map(|e| e.ok())
+    .map(|e| e.path())
+    .filter(|p| p.extension().map(|e| e == "jsonl").unwrap_or(false))
+    .max_by_key(|p| {
+      backup::run_order(&p.file_stem().unwrap_or_default().to_string_lossy())
+    })
+}
+
+pub fn read_journal(path: &Path) -> Result<Vec<Change>, ParoError> {
+  let file = File::open(path).map_err(|err| ParoError::io(err, path))?;
+  BufReader::new(file)
+    .lines()
+    .map(|line| {
+      let line = line.map_err(|err| ParoError::io(err, path))?;
+      serde_json::from_str(&line).map_err(|err| {
+        ParoError::Config(format!("Invalid journal {:?}: {}", path, err))
+      })
+    })
+    .collect()
+}
+
+// an empty journal is dropped, so the next undo goes further back
+pub fn rewrite_journal(
+  path: &Path,
+  changes: &[Change],
+) -> Result<(), ParoError> {
+  fs::remove_file(path).map_err(|err| ParoError::io(err, path))?;
+  let mut journal = Journal::new(path.to_path_buf());
+  changes.iter().try_for_each(|change| journal.record(change))
+}
+
+// one json line per change, the file is only created by the first change
+pub struct Journal {
+  pub path: PathBuf,
+  file: Option<File>,
+}
+
+impl Journal {
+  pub fn new(path: PathBuf) -> Self {
+    Self { path, file: None }
+  }
+
+  pub fn record(&mut self, change: &Change) -> Result<(), ParoError> {
+    let path = &self.path;
+    if self.file.is_none() {
+      if let Some(parent) = path.parent() {
+        files::create_dir(parent)?;
+      }
+      let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| ParoError::io(err, path))?;
+      self.file = Some(file);
+    }
+
+    let line = serde_json::to_string(&change.absolute())
+      .map_err(|err| ParoError::Config(err.to_string()))?;
+    match &mut self.file {
+      Some(file) => {
+        writeln!(file, "{}", line).map_err(|err| ParoError::io(err, path))
+      }
+      None => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_journal() {
+    let state = "tests/journal-state";
+    let _ = fs::remove_dir_all(state);
+    assert_eq!(latest_journal(state), None);
+
+    let changes = vec![
+      Change::Mkdir {
+        path: PathBuf::from("/home/user/.config"),
+      },
+      Change::Delete {
+        path: PathBuf::from("/home/user/.vimrc"),
+        link: None,
+      },
+    ];
+    let mut journal = Journal::new(journal_dir(state).join("1.jsonl"));
+    for change in &changes {
+      journal.record(change).unwrap();
+    }
+    let latest = latest_journal(state).unwrap();
+    let recorded = read_journal(&latest).unwrap();
+    let line = fs::read_to_string(&latest).unwrap();
+    fs::remove_dir_all(state).unwrap();
+
+    assert_eq!(latest, journal.path);
+    assert_eq!(recorded, changes);
+    assert!(line
+      .starts_with("{\"change\":\"mkdir\",\"path\":\"/home/user/.config\"}\n"));
+  }
+
+  #[test]
+  fn test_journal_absolute() {
+    let state = "tests/journal-absolute-state";
+    let _ = fs::remove_dir_all(state);
+    let mut journal = Journal::new(journal_dir(state).join("1.jsonl"));
+    journal
+      .record(&Change::Link {
+        path: PathBuf::from("tests/destination/.normal-file.txt"),
+        origin: PathBuf::from(
+          "tests/example-dotfiles/../example-dotfiles/normal-file.txt",
+        ),
+      })
+      .unwrap();
+    let recorded = read_journal(&journal.path);
+    fs::remove_dir_all(state).unwrap();
+
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(
+      recorded.unwrap(),
+      [Change::Link {
+        path: cwd.join("tests/destination/.normal-file.txt"),
+        origin: fs::canonicalize("tests/example-dotfiles/normal-file.txt")
+          .unwrap(),
+      }]
+    );
+  }
+
+  #[test]
+  fn test_undo() {
+    let dir = Path::new("tests/undo-destination");
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let changes = [
+      Change::Mkdir {
+        path: dir.join(".folder"),
+      },
+      Change::Link {
+        path: dir.join(".folder/file.txt"),
+        origin: PathBuf::from("tests/example-dotfiles/tag-um/file.txt"),
+      },
+    ];
+    fs::write(dir.join(".vimrc"), "local").unwrap();
+    let replaced = Change::remove(&dir.join(".vimrc"), None);
+    for change in changes.iter().chain([&replaced]) {
+      change.apply().unwrap();
+    }
+    for change in changes.iter().rev() {
+      change.undo().unwrap();
+    }
+    let undone = files::is_present(&dir.join(".folder"));
+    let lost = replaced.undo().unwrap_err().exit_code();
+
+    // a link changed to point somewhere else is not ours anymore
+    let relinked = Change::Link {
+      path: dir.join(".vimrc"),
+      origin: PathBuf::from("tests/example-dotfiles/tag-um/file.txt"),
+    };
+    symlink("tag-dois", dir.join(".vimrc")).unwrap();
+    let conflict = relinked.undo().unwrap_err().exit_code();
+    let kept = dir.join(".vimrc").is_symlink();
+    fs::remove_dir_all(dir).unwrap();
+
+    assert!(!undone);
+    assert_eq!(lost, 2);
+    assert_eq!(conflict, 3);
+    assert!(kept);
+  }
+}
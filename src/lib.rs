@@ -0,0 +1,107 @@
+//! Tool for managing dotfiles directories, heavily based on rcm.
+//!
+//! The `paro` binary is a thin layer over this crate, other programs can
+//! build the same [`Settings`], compute a [`Plan`] and apply it without a
+//! terminal:
+//!
+//! ```no_run
+//! use paro::{Action, Actions, ConflictPolicy, FileActions};
+//! use paro::{Operation, Plan, Settings};
+//!
+//! let settings = Settings {
+//!   directories: vec!["/home/user/.dotfiles".to_string()],
+//!   destination: "/home/user".to_string(),
+//!   operation: Operation::Up,
+//!   conflict: ConflictPolicy::Backup,
+//!   ..Default::default()
+//! }
+//! .with_defaults();
+//! let file_actions = FileActions::new(settings).build()?;
+//!
+//! let plan = Plan::new(&file_actions)?;
+//! println!("{}", plan.render("json")?);
+//!
+//! let report = Actions::headless(file_actions).apply()?;
+//! for entry in report.entries {
+//!   if entry.action == Action::Backup {
+//!     println!("backed up {:?}", entry.destination);
+//!   }
+//! }
+//! # Ok::<(), paro::ParoError>(())
+//! ```
+
+pub(crate) mod actions;
+pub(crate) mod backup;
+pub(crate) mod diff;
+pub(crate) mod error;
+pub(crate) mod file_actions;
+pub(crate) mod files;
+pub(crate) mod hooks;
+pub(crate) mod journal;
+pub(crate) mod list;
+pub(crate) mod manifest;
+pub(crate) mod nix_helper;
+pub(crate) mod parsers;
+pub(crate) mod patterns;
+pub(crate) mod plan;
+pub(crate) mod settings;
+pub(crate) mod status;
+pub(crate) mod terminal;
+
+pub use crate::actions::{Actions, Report, ReportEntry};
+pub use crate::error::ParoError;
+pub use crate::file_actions::{FileActions, FileEntry, Method};
+pub use crate::plan::{Action, Plan, PlanEntry};
+pub use crate::settings::{ConflictPolicy, Operation, Settings};
+pub use crate::terminal::{Console, Inputs};
+
+use crate::list::List;
+use crate::parsers::clap::ClapParser;
+use crate::parsers::config::ConfigParser;
+use crate::status::Status;
+
+/// Runs paro with the config files and the command line arguments, this is
+/// all the `paro` binary does.
+pub fn run() -> Result<(), ParoError> {
+  let config_files = nix_helper::get_default_config_files();
+  let config = ConfigParser::new(&config_files)?.into_settings()?;
+  let clap = ClapParser::new().into_settings(vec![])?;
+  let settings = config.merge(clap).with_defaults();
+  match settings.operation {
+    Operation::Add => return Actions::new(FileActions::new(settings)).add(),
+    Operation::Restore => {
+      return Actions::new(FileActions::new(settings)).restore()
+    }
+    Operation::Undo => return Actions::new(FileActions::new(settings)).undo(),
+    _ => {}
+  }
+
+  let files_actions: FileActions = FileActions::new(settings).build()?;
+  let settings = &files_actions.settings;
+  let report_errors = || {
+    for err in &files_actions.errors {
+      eprintln!("ERROR: {}", err);
+    }
+  };
+  match settings.operation {
+    Operation::Status => {
+      report_errors();
+      let status = Status::new(&files_actions)?;
+      println!("{}", status.render(&settings.format, settings.verbose)?);
+      status.check()
+    }
+    Operation::Ls => {
+      report_errors();
+      let list = List::new(&files_actions);
+      println!("{}", list.render(&settings.format, settings.classify)?);
+      Ok(())
+    }
+    _ if settings.plan => {
+      report_errors();
+      let plan = Plan::new(&files_actions)?;
+      println!("{}", plan.render(&settings.format)?);
+      Ok(())
+    }
+    _ => Actions::new(files_actions).execute(),
+  }
+}
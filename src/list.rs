@@ -0,0 +1,130 @@
+use crate::error::ParoError;
+use crate::file_actions::{FileActions, FileEntry, Method};
+use crate::plan;
+use serde::Serialize;
+use std::path::PathBuf;
+
+fn marker(value: &FileEntry) -> &'static str {
+  if value.file_type.is_dir() && !value.symlink_dir {
+    "/"
+  } else if value.method == Method::Copy {
+    "X"
+  } else {
+    "@"
+  }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ListEntry {
+  pub destination: PathBuf,
+  pub source: PathBuf,
+  pub file_type: &'static str,
+  pub method: Method,
+  pub marker: &'static str,
+  pub special_folder: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct List {
+  pub entries: Vec<ListEntry>,
+}
+
+impl List {
+  pub fn new(file_actions: &FileActions) -> Self {
+    let entries = file_actions
+      .actions
+      .iter()
+      // the destination itself is not managed, lsrc leaves it out as well
+      .filter(|(_key, value)| value.depth > 0)
+      .map(|(key, value)| ListEntry {
+        destination: key.clone(),
+        source: value.path.clone(),
+        file_type: plan::file_type(value),
+        method: value.method,
+        marker: marker(value),
+        special_folder: value.special_folder.clone(),
+      })
+      .collect();
+
+    Self { entries }
+  }
+
+  pub fn render(
+    &self,
+    format: &str,
+    classify: bool,
+  ) -> Result<String, ParoError> {
+    if format == "json" {
+      return serde_json::to_string_pretty(self)
+        .map_err(|err| ParoError::Config(err.to_string()));
+    }
+
+    Ok(
+      self
+        .entries
+        .iter()
+        .map(|e| {
+          let mut line = format!(
+            "{}:{}",
+            e.destination.to_string_lossy(),
+            e.source.to_string_lossy()
+          );
+          if classify {
+            line.push(':');
+            line.push_str(e.marker);
+            if let Some(special_folder) = &e.special_folder {
+              line.push(':');
+              line.push_str(special_folder);
+            }
+          }
+          line
+        })
+        .collect::<Vec<String>>()
+        .join("\n"),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::settings::Settings;
+
+  #[test]
+  fn test_list() {
+    let file_actions = FileActions::new(Settings {
+      directories: vec!["tests/example-dotfiles".to_string()],
+      destination: "/home".to_string(),
+      tags: vec!["um".to_string()],
+      copy_includes: vec!["normal-file.txt$".to_string()],
+      excludes: vec!["bin".to_string()],
+      ..Default::default()
+    })
+    .build()
+    .unwrap();
+    let list = List::new(&file_actions);
+
+    assert_eq!(
+      list.render("text", false).unwrap(),
+      [
+        "/home/.file.txt:tests/example-dotfiles/tag-um/file.txt",
+        "/home/.file1.txt:tests/example-dotfiles/tag-um/file1.txt",
+        "/home/.folder:tests/example-dotfiles/folder",
+        "/home/.folder/something.txt:tests/example-dotfiles/folder/something.txt",
+        "/home/.normal-file.txt:tests/example-dotfiles/normal-file.txt",
+      ]
+      .join("\n")
+    );
+    assert_eq!(
+      list.render("text", true).unwrap(),
+      [
+        "/home/.file.txt:tests/example-dotfiles/tag-um/file.txt:@:tag-um",
+        "/home/.file1.txt:tests/example-dotfiles/tag-um/file1.txt:@:tag-um",
+        "/home/.folder:tests/example-dotfiles/folder:/",
+        "/home/.folder/something.txt:tests/example-dotfiles/folder/something.txt:@",
+        "/home/.normal-file.txt:tests/example-dotfiles/normal-file.txt:X",
+      ]
+      .join("\n")
+    );
+  }
+}
@@ -0,0 +1,239 @@
+use crate::error::ParoError;
+use crate::file_actions::{FileActions, FileEntry, Method};
+use crate::files;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const STATE_FILE: &str = "state.json";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+  pub destination: PathBuf,
+  pub source: PathBuf,
+  pub method: Method,
+  pub symlink_dir: bool,
+  pub special_folder: Option<String>,
+}
+
+impl ManifestEntry {
+  // the recorded source is absolute, links to removed dotfiles still match
+  pub fn is_installed(&self) -> bool {
+    match self.method {
+      Method::Link => files::read_link(&self.destination) == self.source,
+      Method::Copy => files::has_same_content(&self.source, &self.destination)
+        .unwrap_or(false),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+  pub timestamp: String,
+  pub tags: Vec<String>,
+  pub hostname: String,
+  pub directories: Vec<String>,
+  pub destination: String,
+  pub entries: Vec<ManifestEntry>,
+}
+
+pub fn state_file(state_dir: &str) -> PathBuf {
+  Path::new(state_dir).join(STATE_FILE)
+}
+
+impl Manifest {
+  pub fn read(state_dir: &str) -> Result<Option<Self>, ParoError> {
+    let path = state_file(state_dir);
+    if !path.exists() {
+      return Ok(None);
+    }
+
+    let content =
+      fs::read_to_string(&path).map_err(|err| ParoError::io(err, &path))?;
+    serde_json::from_str(&content).map(Some).map_err(|err| {
+      ParoError::Config(format!("Invalid state file {:?}: {}", path, err))
+    })
+  }
+
+  pub fn read_required(state_dir: &str) -> Result<Self, ParoError> {
+    Self::read(state_dir)?.ok_or_else(|| {
+      ParoError::Config(format!(
+        "No state file {:?}, run paro up first",
+        state_file(state_dir)
+      ))
+    })
+  }
+
+  pub fn write(&self, state_dir: &str) -> Result<(), ParoError> {
+    let path = state_file(state_dir);
+    files::create_dir(Path::new(state_dir))?;
+    let content = serde_json::to_string_pretty(self)
+      .map_err(|err| ParoError::Config(err.to_string()))?;
+    fs::write(&path, content).map_err(|err| ParoError::io(err, &path))
+  }
+
+  // keeps what is still installed from before and adds what this run installed
+  pub fn update(self, file_actions: &FileActions, timestamp: String) -> Self {
+    let settings = &file_actions.settings;
+    let mut entries: BTreeMap<PathBuf, ManifestEntry> = self
+      .entries
+      .into_iter()
+      .filter(|e| !file_actions.actions.contains_key(&e.destination))
+      .filter(|e| e.is_installed())
+      .map(|e| (e.destination.clone(), e))
+      .collect();
+
+    for (key, value) in &file_actions.actions {
+      if value.path.is_dir() && !value.symlink_dir {
+        continue;
+      }
+      let entry = match fs::canonicalize(&value.path) {
+        Ok(source) => ManifestEntry {
+          destination: key.clone(),
+          source,
+          method: value.method,
+          symlink_dir: value.symlink_dir,
+          special_folder: value.special_folder.clone(),
+        },
+        Err(_) => continue,
+      };
+      if entry.is_installed() {
+        entries.insert(key.clone(), entry);
+      }
+    }
+
+    Self {
+      timestamp,
+      tags: settings.tags.clone(),
+      hostname: settings.hostname.clone(),
+      directories: settings.directories.clone(),
+      destination: settings.destination.clone(),
+      entries: entries.into_values().collect(),
+    }
+  }
+
+  // nothing is recorded for entries that are gone from the destination
+  pub fn to_actions(&self) -> BTreeMap<PathBuf, FileEntry> {
+    self
+      .entries
+      .iter()
+      .filter_map(|e| {
+        let metadata = fs::symlink_metadata(&e.destination).ok()?;
+        Some((
+          e.destination.clone(),
+          FileEntry {
+            path: e.source.clone(),
+            file_type: metadata.file_type(),
+            depth: 0,
+            method: e.method,
+            symlink_dir: e.symlink_dir,
+            special_folder: e.special_folder.clone(),
+          },
+        ))
+      })
+      .collect()
+  }
+
+  // recorded links that the current settings no longer manage
+  pub fn stale_links(
+    &self,
+    actions: &BTreeMap<PathBuf, FileEntry>,
+  ) -> Vec<PathBuf> {
+    self
+      .entries
+      .iter()
+      .filter(|e| e.method == Method::Link)
+      .filter(|e| !actions.contains_key(&e.destination))
+      .filter(|e| e.is_installed())
+      .map(|e| e.destination.clone())
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::settings::Settings;
+  use std::os::unix::fs::symlink;
+
+  #[test]
+  fn test_manifest() {
+    let home = Path::new("tests/manifest-destination");
+    let state = "tests/manifest-state";
+    let _ = fs::remove_dir_all(home);
+    let _ = fs::remove_dir_all(state);
+    fs::create_dir_all(home).unwrap();
+    let file_actions = |tag: &str| {
+      FileActions::new(Settings {
+        directories: vec!["tests/example-dotfiles".to_string()],
+        destination: "tests/manifest-destination".to_string(),
+        excludes: vec!["bin".to_string(), "folder".to_string()],
+        tags: vec![tag.to_string()],
+        ..Default::default()
+      })
+      .build()
+      .unwrap()
+    };
+    let link = |source: &str, name: &str| {
+      symlink(fs::canonicalize(source).unwrap(), home.join(name)).unwrap()
+    };
+
+    // only what is installed gets recorded
+    link("tests/example-dotfiles/tag-um/file1.txt", ".file1.txt");
+    let manifest = Manifest::default().update(&file_actions("um"), "1".into());
+    manifest.write(state).unwrap();
+    let recorded = Manifest::read_required(state).unwrap();
+
+    // entries of the previous tag are kept while installed
+    link("tests/example-dotfiles/tag-dois/file2.txt", ".file2.txt");
+    let manifest = recorded.clone().update(&file_actions("dois"), "2".into());
+    let stale = manifest.stale_links(&file_actions("dois").actions);
+    let actions = manifest.to_actions();
+    fs::remove_dir_all(home).unwrap();
+    fs::remove_dir_all(state).unwrap();
+
+    assert_eq!(recorded, manifest_at("1", "um", &[".file1.txt"], home));
+    assert_eq!(
+      manifest
+        .entries
+        .iter()
+        .map(|e| e.destination.clone())
+        .collect::<Vec<PathBuf>>(),
+      vec![home.join(".file1.txt"), home.join(".file2.txt")]
+    );
+    assert_eq!(manifest.tags, ["dois"]);
+    assert_eq!(stale, vec![home.join(".file1.txt")]);
+    assert_eq!(actions.len(), 2);
+  }
+
+  fn manifest_at(
+    timestamp: &str,
+    tag: &str,
+    names: &[&str],
+    home: &Path,
+  ) -> Manifest {
+    Manifest {
+      timestamp: timestamp.to_string(),
+      tags: vec![tag.to_string()],
+      hostname: "".to_string(),
+      directories: vec!["tests/example-dotfiles".to_string()],
+      destination: "tests/manifest-destination".to_string(),
+      entries: names
+        .iter()
+        .map(|name| ManifestEntry {
+          destination: home.join(name),
+          source: fs::canonicalize(format!(
+            "tests/example-dotfiles/tag-{}/{}",
+            tag,
+            name.trim_start_matches('.')
+          ))
+          .unwrap(),
+          method: Method::Link,
+          symlink_dir: false,
+          special_folder: Some(format!("tag-{}", tag)),
+        })
+        .collect(),
+    }
+  }
+}
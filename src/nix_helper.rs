@@ -28,3 +28,10 @@ pub fn get_default_config_files() -> Vec<String> {
     format!("{}{}", home_dir, "/.dotfiles/config/paro/parorc");
   vec![first_config, second_config, third_config, fourth_config]
 }
+
+pub fn get_state_dir() -> String {
+  match std::env::var("XDG_STATE_HOME") {
+    Ok(state_home) if !state_home.is_empty() => state_home + "/paro",
+    _ => get_user_home() + "/.local/state/paro",
+  }
+}
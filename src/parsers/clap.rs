@@ -1,4 +1,6 @@
-use crate::{files::canonicalize_path, settings::Settings};
+use crate::error::ParoError;
+use crate::files::canonicalize_path;
+use crate::settings::{ConflictPolicy, Operation, Settings};
 use clap::{App, Arg, ArgAction, ArgMatches, Command};
 
 pub struct ClapParser {
@@ -20,6 +22,94 @@ fn to_string_unwrap(matches: &ArgMatches, id: &str) -> String {
     .to_string()
 }
 
+fn flag(matches: &ArgMatches, id: &str) -> bool {
+  matches
+    .try_get_one::<bool>(id)
+    .ok()
+    .flatten()
+    .copied()
+    .unwrap_or(false)
+}
+
+fn to_operation(matches: &ArgMatches) -> Operation {
+  match matches.subcommand_name() {
+    Some("up") => Operation::Up,
+    Some("down") => Operation::Down,
+    Some("status") => Operation::Status,
+    Some("ls") => Operation::Ls,
+    Some("add") => Operation::Add,
+    Some("sync") => Operation::Sync,
+    Some("restore") => Operation::Restore,
+    Some("undo") => Operation::Undo,
+    _ if flag(matches, "down") => Operation::Down,
+    _ => Operation::Up,
+  }
+}
+
+fn force_arg() -> Arg<'static> {
+  Arg::new("force")
+    .short('f')
+    .long("force")
+    .help("Override if the file already exists in your home directory.")
+    .long_help(
+      "Override if the file already exists in your home directory, \
+       does not prompt for how to handle it.",
+    )
+    .action(ArgAction::SetTrue)
+}
+
+fn sync_arg() -> Arg<'static> {
+  Arg::new("sync")
+    .short('S')
+    .long("sync")
+    .help("Remove stale links left behind by deleted or excluded files.")
+    .long_help(
+      "Remove stale links left behind by deleted or excluded files, \
+       any symlink in the destination folder pointing into a dotfiles \
+       directory that is no longer managed by paro is deleted. \
+       Prompts before deleting unless -f is given.",
+    )
+    .action(ArgAction::SetTrue)
+}
+
+fn from_state_arg() -> Arg<'static> {
+  Arg::new("from-state")
+    .long("from-state")
+    .help("Use the files recorded by the last run instead of the settings.")
+    .long_help(
+      "Use the files recorded in <state-dir>/state.json by the last run \
+       instead of the current settings, so down removes every link paro \
+       installed and sync removes the recorded links the current tags \
+       and directories no longer manage, even after they changed.",
+    )
+    .action(ArgAction::SetTrue)
+}
+
+fn skip_hooks_arg() -> Arg<'static> {
+  Arg::new("skip-hooks")
+    .short('K')
+    .long("skip-hooks")
+    .help("Do not run the pre and post hooks.")
+    .long_help(
+      "Do not run the pre and post hooks, \
+       found in the hooks folder of each dotfiles directory.",
+    )
+    .action(ArgAction::SetTrue)
+}
+
+fn plan_arg() -> Arg<'static> {
+  Arg::new("plan")
+    .long("plan")
+    .help("Print the plan of what paro would do and exit.")
+    .long_help(
+      "Print the plan of what paro would do and exit, \
+       one entry per file with its source, destination, file type, \
+       action (keep, mkdir, link, copy, overwrite, backup, skip, \
+       conflict, delete) and the reason for it. Nothing is changed and no hooks run.",
+    )
+    .action(ArgAction::SetTrue)
+}
+
 impl ClapParser {
   pub fn new() -> Self {
     let app = Command::new("paro")
@@ -43,6 +133,7 @@ impl ClapParser {
              This can be repeated with additional patterns.",
           )
           .takes_value(true)
+          .global(true)
           .action(ArgAction::Append),
       )
       .arg(
@@ -53,9 +144,14 @@ impl ClapParser {
           .help("Do not install files that match <file-pattern>.")
           .long_help(
             "Do not install files that match <file-pattern>. \
+             Patterns are globs matched against the path inside the \
+             dotfiles folder, prefix them with re: for a regex \
+             and with dir: to only apply them to the dotfiles folder dir, \
+             or with *: when the pattern contains a colon. \
              This can be repeated with additional patterns.",
           )
           .takes_value(true)
+          .global(true)
           .action(ArgAction::Append),
       )
       .arg(
@@ -66,10 +162,57 @@ impl ClapParser {
           .help("Install files that match <file-pattern>.")
           .long_help(
             "Install files that match <file-pattern>. \
-             Despite being excluded by the -x flag or a setting in the config.
+             Despite being excluded by the -x flag or a setting in the config, \
+             or hidden in the dotfiles folder. \
+             This can be repeated with additional patterns.",
+          )
+          .takes_value(true)
+          .global(true)
+          .action(ArgAction::Append),
+      )
+      .arg(
+        Arg::new("copy-includes")
+          .short('c')
+          .long("include-copy")
+          .value_name("file-pattern")
+          .help("Copy files that match <file-pattern> instead of linking.")
+          .long_help(
+            "Copy files that match <file-pattern> instead of symlinking them, \
+             all the other files are still symlinked. \
+             This can be repeated with additional patterns.",
+          )
+          .takes_value(true)
+          .global(true)
+          .action(ArgAction::Append),
+      )
+      .arg(
+        Arg::new("no-hide")
+          .short('N')
+          .long("no-hide")
+          .value_name("file-pattern")
+          .help("Do not add a leading dot to files that match <file-pattern>.")
+          .long_help(
+            "Do not add a leading dot to the top level files and folders \
+             that match <file-pattern>, useful for folders like bin/. \
+             This can be repeated with additional patterns.",
+          )
+          .takes_value(true)
+          .global(true)
+          .action(ArgAction::Append),
+      )
+      .arg(
+        Arg::new("symlink-dirs")
+          .short('s')
+          .long("symlink-dir")
+          .value_name("folder-pattern")
+          .help("Symlink folders that match <folder-pattern> as a whole.")
+          .long_help(
+            "Symlink folders that match <folder-pattern> as a whole, \
+             instead of creating the folder and linking each of its files. \
              This can be repeated with additional patterns.",
           )
           .takes_value(true)
+          .global(true)
           .action(ArgAction::Append),
       )
       .arg(
@@ -83,6 +226,7 @@ impl ClapParser {
              This can be repeated with additional patterns.",
           )
           .takes_value(true)
+          .global(true)
           .action(ArgAction::Append),
       )
       .arg(
@@ -96,6 +240,7 @@ impl ClapParser {
              By default this value is the current user home directory.",
           )
           .takes_value(true)
+          .global(true)
           .action(ArgAction::Set),
       )
       .arg(
@@ -109,19 +254,67 @@ impl ClapParser {
              Shall return the standard host name for the current machine.",
           )
           .takes_value(true)
+          .global(true)
+          .action(ArgAction::Set),
+      )
+      .arg(
+        Arg::new("copy")
+          .short('C')
+          .long("copy")
+          .help("Copy the files instead of symlinking them.")
+          .long_help(
+            "Copy the files instead of symlinking them. \
+             The contents and permissions of each dotfile are copied into \
+             the destination folder, useful for tools that refuse symlinks.",
+          )
+          .global(true)
+          .action(ArgAction::SetTrue),
+      )
+      .arg(
+        Arg::new("conflict")
+          .long("conflict")
+          .value_name("policy")
+          .help("What to do when a file differs from the dotfile.")
+          .long_help(
+            "What to do when a file in the destination differs from the \
+             dotfile: prompt asks for each file (default), skip keeps the \
+             current file, overwrite replaces it (same as -f), backup \
+             replaces it keeping a backup even with --no-backup and fail \
+             stops before changing anything. Without a terminal paro can \
+             not prompt and keeps the current file.",
+          )
+          .value_parser(["prompt", "skip", "overwrite", "backup", "fail"])
+          .global(true)
           .action(ArgAction::Set),
       )
       .arg(
-        Arg::new("force")
-          .short('f')
-          .long("force")
-          .help("Override if the file already exists in your home directory.")
+        Arg::new("no-backup")
+          .long("no-backup")
+          .help("Delete replaced files instead of backing them up.")
           .long_help(
-            "Override if the file already exists in your home directory, \
-             does not prompt for how to handle it.",
+            "Delete replaced files instead of backing them up. By default \
+             every file paro overwrites or deletes that differs from the \
+             dotfile is moved into a timestamped folder under \
+             <state-dir>/backups, from where paro restore puts it back. \
+             Folders are always backed up.",
           )
+          .global(true)
           .action(ArgAction::SetTrue),
       )
+      .arg(
+        Arg::new("state-dir")
+          .long("state-dir")
+          .value_name("folder-name")
+          .help("Override the folder where paro keeps its state.")
+          .long_help(
+            "Override the folder where paro keeps its state, like the \
+             backups of replaced files. By default this value is \
+             $XDG_STATE_HOME/paro or ~/.local/state/paro.",
+          )
+          .takes_value(true)
+          .global(true)
+          .action(ArgAction::Set),
+      )
       .arg(
         Arg::new("down")
           .short('d')
@@ -133,6 +326,47 @@ impl ClapParser {
           )
           .action(ArgAction::SetTrue),
       )
+      .arg(force_arg())
+      .arg(sync_arg())
+      .arg(from_state_arg())
+      .arg(skip_hooks_arg())
+      .arg(plan_arg())
+      .subcommand(
+        Command::new("up")
+          .about("Install the dotfiles into the destination folder.")
+          .long_about(
+            "Install the dotfiles into the destination folder, \
+             this is the default when no subcommand is given.",
+          )
+          .arg(force_arg())
+          .arg(sync_arg())
+          .arg(skip_hooks_arg())
+          .arg(plan_arg()),
+      )
+      .subcommand(
+        Command::new("down")
+          .about("Remove all the rc files that the paro suite knows about.")
+          .long_about(
+            "Remove all the rc files that the paro suite knows about, \
+             This can be further controlled with the -t, -B and -a flags.",
+          )
+          .arg(force_arg())
+          .arg(from_state_arg())
+          .arg(skip_hooks_arg())
+          .arg(plan_arg()),
+      )
+      .subcommand(
+        Command::new("sync")
+          .about("Install the dotfiles and remove stale links.")
+          .long_about(
+            "Install the dotfiles and remove stale links left behind by \
+             deleted or excluded files, the same as up -S.",
+          )
+          .arg(force_arg())
+          .arg(from_state_arg())
+          .arg(skip_hooks_arg())
+          .arg(plan_arg()),
+      )
       .arg(
         Arg::new("dry-run")
           .short('D')
@@ -142,8 +376,18 @@ impl ClapParser {
             "Shows what paro would do without causing the effects. \
              A simulated or practice performance; rehearsal.",
           )
+          .global(true)
           .action(ArgAction::SetTrue),
       )
+      .arg(
+        Arg::new("format")
+          .long("format")
+          .value_name("format")
+          .help("Output format of the plan and status, text or json.")
+          .value_parser(["text", "json"])
+          .global(true)
+          .action(ArgAction::Set),
+      )
       .arg(
         Arg::new("verbose")
           .short('v')
@@ -153,31 +397,154 @@ impl ClapParser {
             "Make the operation more talkative. \
             This can be repeated for more verbosity.",
           )
+          .global(true)
           .action(ArgAction::Count),
+      )
+      .subcommand(
+        Command::new("status")
+          .about("Show which dotfiles are out of sync with the destination.")
+          .long_about(
+            "Show which dotfiles are out of sync with the destination, \
+             each file is classified as linked, missing, conflict (a real \
+             file is present), elsewhere (a link pointing somewhere else) \
+             or dangling (a broken link). Exits with 5 when anything is \
+             out of sync.",
+          ),
+      )
+      .subcommand(
+        Command::new("add")
+          .about("Move files into the dotfiles directory and link them back.")
+          .long_about(
+            "Move files from the destination folder into the first dotfiles \
+             directory and link them back, the leading dot is removed from \
+             the name. Use -t to place them under tag-<tag> or --host to \
+             place them under host-<hostname>.",
+          )
+          .arg(
+            Arg::new("paths")
+              .value_name("path")
+              .help("Files to add to the dotfiles directory.")
+              .required(true)
+              .multiple_values(true)
+              .action(ArgAction::Append),
+          )
+          .arg(
+            Arg::new("host")
+              .short('o')
+              .long("host")
+              .help("Place the files under host-<hostname>.")
+              .action(ArgAction::SetTrue),
+          ),
+      )
+      .subcommand(
+        Command::new("ls")
+          .about("List the files managed by paro.")
+          .long_about(
+            "List the files managed by paro, one destination:source pair \
+             per line, after applying tags, host, excludes and includes.",
+          )
+          .arg(
+            Arg::new("classify")
+              .short('F')
+              .long("classify")
+              .help("Append markers for the method and special folder.")
+              .long_help(
+                "Append a marker to each line, @ for symlinks, X for copies \
+                 and / for folders, followed by the tag- or host- folder \
+                 the file came from.",
+              )
+              .action(ArgAction::SetTrue),
+          ),
+      )
+      .subcommand(
+        Command::new("restore")
+          .about("Put the files of a backup back into the destination.")
+          .long_about(
+            "Put the files of a backup back into the destination folder, \
+             the latest backup unless <backup> names one of the folders in \
+             <state-dir>/backups. Links created by paro are replaced, real \
+             files are only replaced with -f and are backed up first.",
+          )
+          .arg(
+            Arg::new("backup")
+              .value_name("backup")
+              .help("Name of the backup to restore, defaults to the latest.")
+              .action(ArgAction::Set),
+          )
+          .arg(force_arg()),
+      )
+      .subcommand(
+        Command::new("undo")
+          .about("Roll back the changes of the last run.")
+          .long_about(
+            "Roll back the changes of the last run of up, down, sync, add \
+             or restore, from the journal kept in <state-dir>/journal. \
+             Running it again rolls back the run before that one. Files \
+             changed since that run are left alone and reported.",
+          ),
       );
 
     Self { clap: app }
   }
 
-  pub fn into_settings(self, manual_args: Vec<&str>) -> Settings {
+  pub fn into_settings(
+    self,
+    manual_args: Vec<&str>,
+  ) -> Result<Settings, ParoError> {
     let matches = if manual_args.is_empty() {
       self.clap.get_matches()
     } else {
       self.clap.get_matches_from(manual_args)
     };
-    Settings {
-      tags: to_vec_string(&matches, "tags"),
-      excludes: to_vec_string(&matches, "excludes"),
-      includes: to_vec_string(&matches, "includes"),
-      directories: to_vec_string(&matches, "directories"),
-      destination: canonicalize_path(to_string_unwrap(&matches, "destination"))
-        .unwrap(),
-      hostname: to_string_unwrap(&matches, "hostname"),
-      force: matches.get_one::<bool>("force").copied().unwrap(),
-      down: matches.get_one::<bool>("down").copied().unwrap(),
-      dry_run: matches.get_one::<bool>("dry-run").copied().unwrap(),
-      verbose: matches.get_one::<u8>("verbose").copied().unwrap(),
-    }
+    // global options are propagated into the subcommand matches
+    let sub = matches.subcommand().map(|(_, m)| m).unwrap_or(&matches);
+    let destination = to_string_unwrap(sub, "destination");
+    Ok(Settings {
+      tags: to_vec_string(sub, "tags"),
+      excludes: to_vec_string(sub, "excludes"),
+      includes: to_vec_string(sub, "includes"),
+      copy_includes: to_vec_string(sub, "copy-includes"),
+      no_hide: to_vec_string(sub, "no-hide"),
+      symlink_dirs: to_vec_string(sub, "symlink-dirs"),
+      directories: to_vec_string(sub, "directories"),
+      destination: canonicalize_path(destination.clone())
+        .map_err(|err| ParoError::destination(&destination, err))?,
+      hostname: to_string_unwrap(sub, "hostname"),
+      state_dir: to_string_unwrap(sub, "state-dir"),
+      force: flag(&matches, "force") || flag(sub, "force"),
+      conflict: to_string_unwrap(sub, "conflict")
+        .parse::<ConflictPolicy>()
+        .unwrap_or_default(),
+      no_backup: flag(sub, "no-backup"),
+      copy: flag(sub, "copy"),
+      operation: to_operation(&matches),
+      sync: flag(&matches, "sync") || flag(sub, "sync"),
+      from_state: flag(&matches, "from-state") || flag(sub, "from-state"),
+      skip_hooks: flag(&matches, "skip-hooks") || flag(sub, "skip-hooks"),
+      dry_run: flag(sub, "dry-run"),
+      plan: flag(&matches, "plan") || flag(sub, "plan"),
+      format: to_string_unwrap(sub, "format"),
+      verbose: sub.get_one::<u8>("verbose").copied().unwrap(),
+      add: matches
+        .subcommand_matches("add")
+        .map(|m| to_vec_string(m, "paths"))
+        .unwrap_or_default(),
+      host: flag(sub, "host"),
+      classify: flag(sub, "classify"),
+      restore: matches
+        .subcommand_matches("restore")
+        .map(|m| to_string_unwrap(m, "backup"))
+        .unwrap_or_default(),
+      operation_set: matches.subcommand_name().is_some()
+        || flag(&matches, "down"),
+      conflict_set: !to_string_unwrap(sub, "conflict").is_empty(),
+    })
+  }
+}
+
+impl Default for ClapParser {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
@@ -214,86 +581,312 @@ mod tests {
 
   #[test]
   fn test_clap_defaults() {
-    let settings = ClapParser::new().into_settings(vec!["paro"]);
+    let settings = ClapParser::new().into_settings(vec!["paro"]).unwrap();
     assert_eq!(settings.tags, Vec::<String>::new());
     assert_eq!(settings.excludes, Vec::<String>::new());
     assert_eq!(settings.includes, Vec::<String>::new());
+    assert_eq!(settings.copy_includes, Vec::<String>::new());
+    assert_eq!(settings.no_hide, Vec::<String>::new());
+    assert_eq!(settings.symlink_dirs, Vec::<String>::new());
     assert_eq!(settings.directories, Vec::<String>::new());
     assert_eq!(settings.destination, String::new());
     assert_eq!(settings.hostname, String::new());
-    assert_eq!(settings.force, false);
-    assert_eq!(settings.down, false);
-    assert_eq!(settings.dry_run, false);
+    assert_eq!(settings.state_dir, String::new());
+    assert!(!settings.force);
+    assert_eq!(settings.conflict, ConflictPolicy::Prompt);
+    assert!(!settings.no_backup);
+    assert!(!settings.copy);
+    assert_eq!(settings.operation, Operation::Up);
+    assert!(!settings.sync);
+    assert!(!settings.from_state);
+    assert!(!settings.skip_hooks);
+    assert!(!settings.dry_run);
+    assert!(!settings.plan);
+    assert_eq!(settings.format, String::new());
     assert_eq!(settings.verbose, 0);
+    assert_eq!(settings.add, Vec::<String>::new());
+    assert!(!settings.host);
+    assert!(!settings.classify);
+    assert_eq!(settings.restore, String::new());
+    assert!(!settings.operation_set);
+    assert!(!settings.conflict_set);
   }
 
   #[test]
   fn test_clap_tags() {
-    let settings =
-      ClapParser::new().into_settings(vec!["paro", "-t", "linux1"]);
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "-t", "linux1"])
+      .unwrap();
     assert_eq!(settings.tags, ["linux1"]);
   }
 
   #[test]
   fn test_clap_excludes() {
-    let settings = ClapParser::new().into_settings(vec![
-      "paro",
-      "-x",
-      "file.txt",
-      "-x",
-      "file2.txt",
-      "-x",
-      "file3.txt",
-    ]);
+    let settings = ClapParser::new()
+      .into_settings(vec![
+        "paro",
+        "-x",
+        "file.txt",
+        "-x",
+        "file2.txt",
+        "-x",
+        "file3.txt",
+      ])
+      .unwrap();
     assert_eq!(settings.excludes, ["file.txt", "file2.txt", "file3.txt"]);
   }
 
   #[test]
   fn test_clap_includes() {
-    let settings = ClapParser::new().into_settings(vec![
-      "paro",
-      "-i",
-      "file.txt",
-      "-i",
-      "file2.txt",
-      "-i",
-      "file3.txt",
-    ]);
+    let settings = ClapParser::new()
+      .into_settings(vec![
+        "paro",
+        "-i",
+        "file.txt",
+        "-i",
+        "file2.txt",
+        "-i",
+        "file3.txt",
+      ])
+      .unwrap();
     assert_eq!(settings.includes, ["file.txt", "file2.txt", "file3.txt"]);
   }
 
+  #[test]
+  fn test_clap_copy_includes() {
+    let settings = ClapParser::new()
+      .into_settings(vec![
+        "paro",
+        "-c",
+        "file.txt",
+        "--include-copy",
+        "file2.txt",
+      ])
+      .unwrap();
+    assert_eq!(settings.copy_includes, ["file.txt", "file2.txt"]);
+  }
+
+  #[test]
+  fn test_clap_no_hide() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "-N", "bin", "-N", "lib"])
+      .unwrap();
+    assert_eq!(settings.no_hide, ["bin", "lib"]);
+  }
+
+  #[test]
+  fn test_clap_symlink_dirs() {
+    let settings = ClapParser::new()
+      .into_settings(vec![
+        "paro",
+        "-s",
+        "config/nvim",
+        "--symlink-dir",
+        "emacs.d",
+      ])
+      .unwrap();
+    assert_eq!(settings.symlink_dirs, ["config/nvim", "emacs.d"]);
+  }
+
   #[test]
   fn test_clap_directories() {
-    let settings = ClapParser::new().into_settings(vec![
-      "paro", "-a", "home/", "-a", "dome/", "-a", "pombe/",
-    ]);
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "-a", "home/", "-a", "dome/", "-a", "pombe/"])
+      .unwrap();
     assert_eq!(settings.directories, ["home/", "dome/", "pombe/"]);
   }
 
   #[test]
   fn test_clap_hostname() {
-    let settings = ClapParser::new().into_settings(vec![
-      "paro",
-      "-B",
-      "my-machine",
-      "-B",
-      "will-override-my-machine",
-    ]);
+    let settings = ClapParser::new()
+      .into_settings(vec![
+        "paro",
+        "-B",
+        "my-machine",
+        "-B",
+        "will-override-my-machine",
+      ])
+      .unwrap();
     assert_eq!(settings.hostname, "will-override-my-machine");
   }
 
   #[test]
   fn test_clap_destination() {
-    let settings =
-      ClapParser::new().into_settings(vec!["paro", "-n", "src", "-n", "tests"]);
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "-n", "src", "-n", "tests"])
+      .unwrap();
 
     let re = Regex::new(r"tests$").unwrap();
     assert!(re.is_match(settings.destination.as_str()));
   }
 
+  #[test]
+  fn test_clap_copy() {
+    let settings = ClapParser::new().into_settings(vec!["paro", "-C"]).unwrap();
+    assert!(settings.copy);
+  }
+
+  #[test]
+  fn test_clap_sync() {
+    let settings = ClapParser::new().into_settings(vec!["paro", "-S"]).unwrap();
+    assert!(settings.sync);
+  }
+
+  #[test]
+  fn test_clap_conflict() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "up", "--conflict", "skip"])
+      .unwrap();
+    assert_eq!(settings.conflict, ConflictPolicy::Skip);
+    assert!(settings.conflict_set);
+
+    // even the default is an explicit choice over the config
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "up", "--conflict", "prompt"])
+      .unwrap();
+    assert_eq!(settings.conflict, ConflictPolicy::Prompt);
+    assert!(settings.conflict_set);
+  }
+
+  #[test]
+  fn test_clap_down() {
+    let settings = ClapParser::new().into_settings(vec!["paro", "-d"]).unwrap();
+    assert_eq!(settings.operation, Operation::Down);
+
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "down", "-f", "-K", "--from-state"])
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Down);
+    assert!(settings.from_state);
+    assert!(settings.force);
+    assert!(settings.skip_hooks);
+  }
+
+  #[test]
+  fn test_clap_up() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "up", "-S", "--plan", "-t", "dois"])
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Up);
+    assert!(settings.operation_set);
+    assert_eq!(settings.tags, ["dois"]);
+    assert!(settings.sync);
+    assert!(settings.plan);
+
+    // legacy flags before the subcommand still apply
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "-f", "-t", "um", "up", "-v"])
+      .unwrap();
+    assert!(settings.force);
+    assert_eq!(settings.tags, ["um"]);
+    assert_eq!(settings.verbose, 1);
+  }
+
+  #[test]
+  fn test_clap_sync_subcommand() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "sync", "-f"])
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Sync);
+    assert!(settings.force);
+    assert!(!settings.sync);
+  }
+
+  #[test]
+  fn test_clap_skip_hooks() {
+    let settings = ClapParser::new().into_settings(vec!["paro", "-K"]).unwrap();
+    assert!(settings.skip_hooks);
+  }
+
+  #[test]
+  fn test_clap_invalid_destination() {
+    let error = ClapParser::new()
+      .into_settings(vec!["paro", "-n", "non-existing-folder"])
+      .unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "Destination folder \"non-existing-folder\" does not exist"
+    );
+  }
+
+  #[test]
+  fn test_clap_plan() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "--plan", "--format", "json"])
+      .unwrap();
+    assert!(settings.plan);
+    assert_eq!(settings.format, "json");
+  }
+
   #[test]
   fn test_clap_verbose() {
-    let settings = ClapParser::new().into_settings(vec!["paro", "-vv"]);
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "-vv"])
+      .unwrap();
     assert_eq!(settings.verbose, 2);
   }
+
+  #[test]
+  fn test_clap_status() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "-a", "home/", "status"])
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Status);
+    assert_eq!(settings.directories, ["home/"]);
+  }
+
+  #[test]
+  fn test_clap_add() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "add", "--host", ".vimrc", ".zshrc"])
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Add);
+    assert_eq!(settings.add, [".vimrc", ".zshrc"]);
+    assert!(settings.host);
+  }
+
+  #[test]
+  fn test_clap_ls() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "-t", "git", "ls", "-F"])
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Ls);
+    assert!(settings.classify);
+    assert_eq!(settings.tags, ["git"]);
+  }
+
+  #[test]
+  fn test_clap_restore() {
+    let settings = ClapParser::new()
+      .into_settings(vec![
+        "paro",
+        "--state-dir",
+        "/tmp/paro",
+        "restore",
+        "-f",
+        "2026-10-18T12-34-56",
+      ])
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Restore);
+    assert_eq!(settings.restore, "2026-10-18T12-34-56");
+    assert_eq!(settings.state_dir, "/tmp/paro");
+    assert!(settings.force);
+  }
+
+  #[test]
+  fn test_clap_undo() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "undo", "-D"])
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Undo);
+    assert!(settings.dry_run);
+  }
+
+  #[test]
+  fn test_clap_no_backup() {
+    let settings = ClapParser::new()
+      .into_settings(vec!["paro", "up", "--no-backup"])
+      .unwrap();
+    assert!(settings.no_backup);
+  }
 }
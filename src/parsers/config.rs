@@ -1,13 +1,73 @@
-use crate::{files::canonicalize_path, settings::Settings};
-use config::{Config, File, FileFormat};
+use crate::{error::ParoError, files::canonicalize_path, settings::Settings};
+use config::builder::DefaultState;
+use config::{
+  Config, ConfigBuilder, ConfigError, File, FileFormat, Map, Value,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 pub struct ConfigParser {
   config: Config,
+  files: Vec<String>,
+}
+
+fn with_defaults(
+  builder: ConfigBuilder<DefaultState>,
+) -> Result<ConfigBuilder<DefaultState>, ConfigError> {
+  builder
+    .set_default("tags", Vec::<String>::new())?
+    .set_default("excludes", Vec::<String>::new())?
+    .set_default("includes", Vec::<String>::new())?
+    .set_default("copy-includes", Vec::<String>::new())?
+    .set_default("no-hide", Vec::<String>::new())?
+    .set_default("symlink-dirs", Vec::<String>::new())?
+    .set_default("directories", Vec::<String>::new())?
+    .set_default("destination", String::new())?
+    .set_default("hostname", String::new())?
+    .set_default("state-dir", String::new())?
+    .set_default("force", false)?
+    .set_default("conflict", "prompt")?
+    .set_default("no-backup", false)?
+    .set_default("copy", false)?
+    .set_default("down", false)?
+    .set_default("sync", false)?
+    .set_default("from-state", false)?
+    .set_default("skip-hooks", false)?
+    .set_default("dry-run", false)?
+    .set_default("verbose", 0)
+}
+
+fn resolve_file(file: &str) -> Option<PathBuf> {
+  [PathBuf::from(file), PathBuf::from(format!("{}.toml", file))]
+    .into_iter()
+    .find(|path| path.is_file())
+}
+
+fn find_key_line(path: &Path, key: &str) -> Option<usize> {
+  fs::read_to_string(path)
+    .ok()?
+    .lines()
+    .position(|line| {
+      line
+        .trim_start()
+        .strip_prefix(key)
+        .map(|rest| rest.trim_start().starts_with('='))
+        .unwrap_or(false)
+    })
+    .map(|index| index + 1)
+}
+
+fn check_key(key: &str, value: Value) -> Result<(), ConfigError> {
+  with_defaults(Config::builder())?
+    .set_override(key, value)?
+    .build()?
+    .try_deserialize::<Settings>()
+    .map(|_| ())
 }
 
 impl ConfigParser {
-  pub fn new(files: &Vec<String>) -> Self {
-    let mut builder = Config::builder();
+  pub fn new(files: &Vec<String>) -> Result<Self, ParoError> {
+    let mut builder = with_defaults(Config::builder())?;
 
     for file in files {
       builder = builder.add_source(
@@ -15,45 +75,63 @@ impl ConfigParser {
       );
     }
 
-    Self {
-      config: builder
-        .set_default("tags", Vec::<String>::new())
-        .unwrap()
-        .set_default("excludes", Vec::<String>::new())
-        .unwrap()
-        .set_default("includes", Vec::<String>::new())
-        .unwrap()
-        .set_default("directories", Vec::<String>::new())
-        .unwrap()
-        .set_default("destination", String::new())
-        .unwrap()
-        .set_default("hostname", String::new())
-        .unwrap()
-        .set_default("force", false)
-        .unwrap()
-        .set_default("down", false)
-        .unwrap()
-        .set_default("dry-run", false)
-        .unwrap()
-        .set_default("verbose", 0)
-        .unwrap()
+    let config = builder.build().map_err(|err| {
+      ParoError::Config(format!("Invalid config file: {}", err))
+    })?;
+
+    Ok(Self {
+      config,
+      files: files.to_owned(),
+    })
+  }
+
+  fn diagnose(&self, error: ConfigError) -> ParoError {
+    for path in self.files.iter().filter_map(|file| resolve_file(file)) {
+      let table = match Config::builder()
+        .add_source(File::from(path.as_path()).format(FileFormat::Toml))
         .build()
-        .unwrap(),
+        .and_then(|config| config.try_deserialize::<Map<String, Value>>())
+      {
+        Ok(table) => table,
+        Err(_) => continue,
+      };
+
+      for (key, value) in table {
+        if let Err(err) = check_key(&key, value) {
+          let line = find_key_line(&path, &key)
+            .map(|line| format!(":{}", line))
+            .unwrap_or_default();
+          return ParoError::Config(format!(
+            "Invalid config file {}{}: key `{}`: {}",
+            path.display(),
+            line,
+            key,
+            err
+          ));
+        }
+      }
     }
+
+    ParoError::Config(format!("Invalid config: {}", error))
   }
 
-  pub fn into_settings(self) -> Settings {
-    let settings: Settings = self.config.try_deserialize().unwrap();
-    Settings {
-      destination: canonicalize_path(settings.destination).unwrap(),
+  pub fn into_settings(self) -> Result<Settings, ParoError> {
+    let settings: Settings = match self.config.clone().try_deserialize() {
+      Ok(settings) => settings,
+      Err(err) => return Err(self.diagnose(err)),
+    };
+    Ok(Settings {
+      destination: canonicalize_path(settings.destination.clone())
+        .map_err(|err| ParoError::destination(&settings.destination, err))?,
       ..settings
-    }
+    })
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::settings::{ConflictPolicy, Operation};
   use regex::Regex;
 
   fn config_file() -> Vec<String> {
@@ -62,53 +140,120 @@ mod tests {
 
   #[test]
   fn test_config_defaults() {
-    let settings =
-      ConfigParser::new(&vec!["tests/non-exist".to_string()]).into_settings();
+    let settings = ConfigParser::new(&vec!["tests/non-exist".to_string()])
+      .unwrap()
+      .into_settings()
+      .unwrap();
     assert_eq!(settings.tags, Vec::<String>::new());
     assert_eq!(settings.excludes, Vec::<String>::new());
     assert_eq!(settings.includes, Vec::<String>::new());
+    assert_eq!(settings.copy_includes, Vec::<String>::new());
+    assert_eq!(settings.no_hide, Vec::<String>::new());
+    assert_eq!(settings.symlink_dirs, Vec::<String>::new());
     assert_eq!(settings.directories, Vec::<String>::new());
     assert_eq!(settings.destination, String::new());
     assert_eq!(settings.hostname, String::new());
-    assert_eq!(settings.force, false);
-    assert_eq!(settings.down, false);
-    assert_eq!(settings.dry_run, false);
+    assert!(!settings.force);
+    assert_eq!(settings.conflict, ConflictPolicy::Prompt);
+    assert!(!settings.copy);
+    assert_eq!(settings.operation, Operation::Up);
+    assert!(!settings.sync);
+    assert!(!settings.skip_hooks);
+    assert!(!settings.dry_run);
+    assert!(!settings.plan);
+    assert_eq!(settings.format, String::new());
     assert_eq!(settings.verbose, 0);
   }
 
   #[test]
   fn test_config_tags() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
     assert_eq!(settings.tags, ["linux1", "macos2"]);
   }
 
   #[test]
   fn test_config_excludes() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
     assert_eq!(settings.excludes, ["file.txt", "file2.txt", "file3.txt"]);
   }
 
   #[test]
   fn test_config_includes() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
     assert_eq!(settings.includes, ["file.txt", "file2.txt", "file3.txt"]);
   }
 
+  #[test]
+  fn test_config_copy_includes() {
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert_eq!(settings.copy_includes, ["file.txt"]);
+  }
+
+  #[test]
+  fn test_config_no_hide() {
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert_eq!(settings.no_hide, ["bin"]);
+  }
+
+  #[test]
+  fn test_config_symlink_dirs() {
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert_eq!(settings.symlink_dirs, ["config/nvim"]);
+  }
+
   #[test]
   fn test_config_directories() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
     assert_eq!(settings.directories, ["home/", "dome/", "pombe/"]);
   }
 
   #[test]
   fn test_config_hostname() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
     assert_eq!(settings.hostname, "hostname-in-config");
   }
 
+  #[test]
+  fn test_config_state_dir() {
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert_eq!(settings.state_dir, "/tmp/paro-state");
+    assert!(settings.no_backup);
+    assert!(settings.from_state);
+  }
+
   #[test]
   fn test_config_destination() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
 
     let re = Regex::new(r"/tests$").unwrap();
     assert!(re.is_match(settings.destination.as_str()));
@@ -116,25 +261,121 @@ mod tests {
 
   #[test]
   fn test_config_force() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
-    assert_eq!(settings.force, true);
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert!(settings.force);
+  }
+
+  #[test]
+  fn test_config_copy() {
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert!(settings.copy);
   }
 
   #[test]
   fn test_config_down() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
-    assert_eq!(settings.down, true);
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert_eq!(settings.operation, Operation::Down);
+  }
+
+  #[test]
+  fn test_config_sync() {
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert!(settings.sync);
+  }
+
+  #[test]
+  fn test_config_skip_hooks() {
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert!(settings.skip_hooks);
   }
 
   #[test]
   fn test_config_dry_run() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
-    assert_eq!(settings.dry_run, true);
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert!(settings.dry_run);
+  }
+
+  #[test]
+  fn test_config_plan() {
+    assert!(invalid_config_error("plan").starts_with(
+      "Invalid config file tests/invalid-settings/plan.toml:2: \
+       key `plan`: unknown field `plan`"
+    ));
+  }
+
+  #[test]
+  fn test_config_conflict() {
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
+    assert_eq!(settings.conflict, ConflictPolicy::Overwrite);
   }
 
   #[test]
   fn test_config_verbose() {
-    let settings = ConfigParser::new(&config_file()).into_settings();
+    let settings = ConfigParser::new(&config_file())
+      .unwrap()
+      .into_settings()
+      .unwrap();
     assert_eq!(settings.verbose, 2);
   }
+
+  fn invalid_config_error(name: &str) -> String {
+    ConfigParser::new(&vec![format!("tests/invalid-settings/{}", name)])
+      .and_then(|config| config.into_settings())
+      .unwrap_err()
+      .to_string()
+  }
+
+  #[test]
+  fn test_config_invalid_syntax() {
+    let error = invalid_config_error("syntax");
+    assert!(error.starts_with("Invalid config file: "));
+    assert!(error.contains("at line 2"));
+    assert!(error.contains("tests/invalid-settings/syntax"));
+  }
+
+  #[test]
+  fn test_config_invalid_type() {
+    assert_eq!(
+      invalid_config_error("type"),
+      "Invalid config file tests/invalid-settings/type.toml:2: key `tags`: \
+       invalid type: string \"linux\", expected a sequence"
+    );
+  }
+
+  #[test]
+  fn test_config_unknown_key() {
+    assert!(invalid_config_error("unknown").starts_with(
+      "Invalid config file tests/invalid-settings/unknown.toml:2: \
+       key `forse`: unknown field `forse`"
+    ));
+  }
+
+  #[test]
+  fn test_config_invalid_destination() {
+    assert_eq!(
+      invalid_config_error("destination"),
+      "Destination folder \"./non-existing-folder\" does not exist"
+    );
+  }
 }
@@ -0,0 +1,233 @@
+use crate::error::ParoError;
+use regex::Regex;
+use std::fs;
+use std::io::ErrorKind;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+pub const REGEX_PREFIX: &str = "re:";
+pub const IGNORE_FILE: &str = ".paroignore";
+
+// a glob like in rcm or gitignore, or a regex when prefixed with re:
+#[derive(Clone, Debug)]
+pub struct Pattern {
+  regex: Regex,
+  raw: bool,
+  negated: bool,
+}
+
+impl Pattern {
+  pub fn new(pattern: &str) -> Result<Self, ParoError> {
+    match pattern.strip_prefix(REGEX_PREFIX) {
+      Some(regex) => Ok(Self {
+        regex: Regex::new(regex)?,
+        raw: true,
+        negated: false,
+      }),
+      None => Self::glob(pattern),
+    }
+  }
+
+  pub fn glob(glob: &str) -> Result<Self, ParoError> {
+    Ok(Self {
+      regex: Regex::new(&glob_to_regex(glob))?,
+      raw: false,
+      negated: false,
+    })
+  }
+
+  // globs see folders with a trailing slash so that dir/ only matches folders
+  pub fn is_match(&self, relative: &Path, is_dir: bool) -> bool {
+    let path = relative.to_string_lossy();
+    if is_dir && !self.raw {
+      self.regex.is_match(&format!("{}/", path))
+    } else {
+      self.regex.is_match(&path)
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Patterns(Vec<Pattern>);
+
+impl Patterns {
+  pub fn new(patterns: &[String]) -> Result<Self, ParoError> {
+    patterns
+      .iter()
+      .map(|pattern| Pattern::new(pattern))
+      .collect::<Result<_, _>>()
+      .map(Self)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  // the last matching pattern decides, like in gitignore
+  pub fn is_match(&self, relative: &Path, is_dir: bool) -> bool {
+    self
+      .0
+      .iter()
+      .rev()
+      .find(|pattern| pattern.is_match(relative, is_dir))
+      .map(|pattern| !pattern.negated)
+      .unwrap_or(false)
+  }
+
+  // gitignore syntax: comments, blank lines and !pattern to take files back
+  pub fn parse_ignore(content: &str) -> Result<Self, ParoError> {
+    content
+      .lines()
+      .map(|line| line.trim_end())
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(|line| match line.strip_prefix('!') {
+        Some(glob) => Ok(Pattern {
+          negated: true,
+          ..Pattern::glob(glob)?
+        }),
+        None => Pattern::glob(line),
+      })
+      .collect::<Result<_, _>>()
+      .map(Self)
+  }
+
+  pub fn read_ignore(dir: &str) -> Result<Self, ParoError> {
+    let path = Path::new(dir).join(IGNORE_FILE);
+    match fs::read_to_string(&path) {
+      Ok(content) => Self::parse_ignore(&content),
+      Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+      Err(err) => Err(ParoError::io(err, &path)),
+    }
+  }
+}
+
+// patterns without a slash match at any depth, the others from the top
+pub fn glob_to_regex(glob: &str) -> String {
+  let dir_only = glob.ends_with('/');
+  let glob = glob.trim_end_matches('/');
+  let anchored = glob.contains('/');
+  let glob = glob.trim_start_matches('/');
+
+  let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+  let mut chars = glob.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '*' if chars.peek() == Some(&'*') => {
+        chars.next();
+        if chars.peek() == Some(&'/') {
+          chars.next();
+          regex.push_str("(?:.*/)?");
+        } else {
+          regex.push_str(".*");
+        }
+      }
+      '*' => regex.push_str("[^/]*"),
+      '?' => regex.push_str("[^/]"),
+      '[' => match char_class(&mut chars) {
+        Some(class) => regex.push_str(&class),
+        None => regex.push_str(r"\["),
+      },
+      '\\' => {
+        if let Some(escaped) = chars.next() {
+          regex.push_str(&regex::escape(&escaped.to_string()));
+        }
+      }
+      _ => regex.push_str(&regex::escape(&c.to_string())),
+    }
+  }
+  // a matching folder takes everything inside with it
+  regex.push_str(if dir_only { "/.*$" } else { "(?:/.*)?$" });
+  regex
+}
+
+fn char_class(chars: &mut Peekable<Chars>) -> Option<String> {
+  let mut rest = chars.clone();
+  let mut class = String::from("[");
+  if matches!(rest.peek(), Some('!') | Some('^')) {
+    rest.next();
+    class.push_str("^/");
+  }
+
+  let mut first = true;
+  loop {
+    match rest.next()? {
+      ']' if !first => break,
+      '-' => class.push('-'),
+      c => class.push_str(&regex::escape(&c.to_string())),
+    }
+    first = false;
+  }
+  class.push(']');
+  *chars = rest;
+  Some(class)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn is_match(pattern: &str, path: &str, is_dir: bool) -> bool {
+    Pattern::new(pattern)
+      .unwrap()
+      .is_match(Path::new(path), is_dir)
+  }
+
+  #[test]
+  fn test_glob_to_regex() {
+    assert_eq!(glob_to_regex("file.txt"), r"^(?:.*/)?file\.txt(?:/.*)?$");
+    assert_eq!(glob_to_regex("/bin/*.sh"), r"^bin/[^/]*\.sh(?:/.*)?$");
+    assert_eq!(glob_to_regex("**/f?[!a-c]/"), r"^(?:.*/)?f[^/][^/a-c]/.*$");
+    assert_eq!(glob_to_regex("[oops"), r"^(?:.*/)?\[oops(?:/.*)?$");
+  }
+
+  #[test]
+  fn test_pattern() {
+    // dots are literal and names are matched whole at any depth
+    assert!(is_match("file.txt", "file.txt", false));
+    assert!(is_match("file.txt", "tag-um/file.txt", false));
+    assert!(!is_match("file.txt", "profile.txt", false));
+    assert!(!is_match("file.txt", "file_txt", false));
+
+    // a slash anchors the pattern to the dotfiles folder
+    assert!(is_match("/file.txt", "file.txt", false));
+    assert!(!is_match("/file.txt", "tag-um/file.txt", false));
+    assert!(is_match("config/*.vim", "config/init.vim", false));
+    assert!(!is_match("config/*.vim", "config/nvim/init.vim", false));
+    assert!(is_match("config/**/*.vim", "config/nvim/init.vim", false));
+    assert!(is_match("**/init.vim", "config/nvim/init.vim", false));
+
+    // folders match with their content, a trailing slash only matches folders
+    assert!(is_match("bin", "bin/paro-script", false));
+    assert!(is_match("bin/", "bin", true));
+    assert!(is_match("bin/", "bin/paro-script", false));
+    assert!(!is_match("bin/", "bin", false));
+
+    // regexes are matched as they are
+    assert!(is_match("re:^tag-.*\\.txt$", "tag-um/file.txt", false));
+    assert!(is_match("re:file.txt", "profile.txt", false));
+    assert!(Pattern::new("re:(").is_err());
+  }
+
+  #[test]
+  fn test_parse_ignore() {
+    let ignore = Patterns::parse_ignore(
+      "# shipped with the repo\n\
+       README.md \n\
+       \n\
+       *.md\n\
+       !docs/*.md\n\
+       \\#notes\n\
+       re:vimrc\n",
+    )
+    .unwrap();
+    let is_ignored = |path: &str| ignore.is_match(Path::new(path), false);
+
+    assert!(is_ignored("README.md"));
+    assert!(is_ignored("config/notes.md"));
+    assert!(!is_ignored("docs/notes.md"));
+    assert!(is_ignored("#notes"));
+    assert!(!is_ignored("vimrc"));
+    assert!(is_ignored("re:vimrc"));
+  }
+}
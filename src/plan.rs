@@ -0,0 +1,369 @@
+use crate::error::ParoError;
+use crate::file_actions::{FileActions, FileEntry, Method};
+use crate::files;
+use crate::settings::{ConflictPolicy, Operation};
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+  Keep,
+  Mkdir,
+  Link,
+  Copy,
+  Overwrite,
+  Backup,
+  Skip,
+  Conflict,
+  Delete,
+  Restore,
+}
+
+impl fmt::Display for Action {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Action::Keep => write!(f, "keep"),
+      Action::Mkdir => write!(f, "mkdir"),
+      Action::Link => write!(f, "link"),
+      Action::Copy => write!(f, "copy"),
+      Action::Overwrite => write!(f, "overwrite"),
+      Action::Backup => write!(f, "backup"),
+      Action::Skip => write!(f, "skip"),
+      Action::Conflict => write!(f, "conflict"),
+      Action::Delete => write!(f, "delete"),
+      Action::Restore => write!(f, "restore"),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decision {
+  pub action: Action,
+  pub reason: &'static str,
+}
+
+fn decision(action: Action, reason: &'static str) -> Decision {
+  Decision { action, reason }
+}
+
+fn install(value: &FileEntry) -> Action {
+  match value.method {
+    Method::Link => Action::Link,
+    Method::Copy => Action::Copy,
+  }
+}
+
+pub fn decide_up(
+  value: &FileEntry,
+  key: &Path,
+  policy: ConflictPolicy,
+) -> io::Result<Decision> {
+  let copy = value.method == Method::Copy;
+  if files::is_same_file(&value.path, key, copy)? {
+    return Ok(decision(Action::Keep, "already installed"));
+  }
+
+  if value.path.is_dir() && !value.symlink_dir {
+    return Ok(if key.exists() {
+      decision(Action::Keep, "folder already exists")
+    } else {
+      decision(Action::Mkdir, "folder is missing")
+    });
+  }
+
+  if !files::is_present(key) {
+    return Ok(decision(install(value), "not installed"));
+  }
+
+  Ok(match policy {
+    ConflictPolicy::Overwrite => {
+      decision(Action::Overwrite, "destination differs, overwrite")
+    }
+    ConflictPolicy::Backup => {
+      decision(Action::Backup, "destination differs, backup")
+    }
+    ConflictPolicy::Skip => decision(Action::Skip, "destination differs, skip"),
+    _ => decision(Action::Conflict, "destination differs"),
+  })
+}
+
+pub fn decide_down(
+  value: &FileEntry,
+  key: &Path,
+  policy: ConflictPolicy,
+) -> io::Result<Decision> {
+  let copy = value.method == Method::Copy;
+  // links recorded in the state file may point to removed dotfiles
+  if files::is_same_file(&value.path, key, copy)?
+    || (!copy && files::read_link(key) == value.path)
+  {
+    return Ok(decision(Action::Delete, "installed by paro"));
+  }
+
+  if value.path.is_dir() && !value.symlink_dir {
+    return Ok(decision(
+      Action::Keep,
+      "folders are removed when left empty",
+    ));
+  }
+
+  if !files::is_present(key) {
+    return Ok(decision(Action::Keep, "not installed"));
+  }
+
+  Ok(match policy {
+    ConflictPolicy::Overwrite => {
+      decision(Action::Delete, "destination differs, overwrite")
+    }
+    ConflictPolicy::Backup => {
+      decision(Action::Backup, "destination differs, backup")
+    }
+    ConflictPolicy::Skip => decision(Action::Skip, "destination differs, skip"),
+    _ => decision(Action::Conflict, "destination differs"),
+  })
+}
+
+pub fn file_type(value: &FileEntry) -> &'static str {
+  if value.file_type.is_dir() {
+    "dir"
+  } else if value.file_type.is_symlink() {
+    "symlink"
+  } else {
+    "file"
+  }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlanEntry {
+  pub source: PathBuf,
+  pub destination: PathBuf,
+  pub file_type: &'static str,
+  pub method: Method,
+  pub action: Action,
+  pub reason: &'static str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Plan {
+  pub operation: Operation,
+  pub entries: Vec<PlanEntry>,
+}
+
+impl Plan {
+  pub fn new(file_actions: &FileActions) -> Result<Self, ParoError> {
+    let settings = &file_actions.settings;
+    let mut entries = Vec::new();
+
+    for (key, value) in &file_actions.actions {
+      let policy = settings.conflict_policy();
+      let decision = if settings.operation == Operation::Down {
+        decide_down(value, key, policy)
+      } else {
+        decide_up(value, key, policy)
+      }
+      .map_err(|err| ParoError::io(err, key))?;
+
+      entries.push(PlanEntry {
+        source: value.path.clone(),
+        destination: key.clone(),
+        file_type: file_type(value),
+        method: value.method,
+        action: decision.action,
+        reason: decision.reason,
+      });
+    }
+
+    if settings.removes_stale_links() {
+      for key in file_actions.stale_links()? {
+        entries.push(PlanEntry {
+          source: files::read_link(&key),
+          destination: key,
+          file_type: "symlink",
+          method: Method::Link,
+          action: Action::Delete,
+          reason: "stale link",
+        });
+      }
+    }
+
+    Ok(Self {
+      operation: settings.operation,
+      entries,
+    })
+  }
+
+  pub fn conflicts(&self) -> Vec<PathBuf> {
+    self
+      .entries
+      .iter()
+      .filter(|e| e.action == Action::Conflict)
+      .map(|e| e.destination.clone())
+      .collect()
+  }
+
+  pub fn render(&self, format: &str) -> Result<String, ParoError> {
+    match format {
+      "json" => serde_json::to_string_pretty(self)
+        .map_err(|err| ParoError::Config(err.to_string())),
+      _ => Ok(
+        self
+          .entries
+          .iter()
+          .map(|e| {
+            format!(
+              "{} {:?} -> {:?} ({})",
+              e.action, e.source, e.destination, e.reason
+            )
+          })
+          .collect::<Vec<String>>()
+          .join("\n"),
+      ),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::settings::Settings;
+
+  fn build_plan(settings: Settings) -> Plan {
+    let file_actions = FileActions::new(settings).build().unwrap();
+    Plan::new(&file_actions).unwrap()
+  }
+
+  #[test]
+  fn test_plan_up() {
+    let plan = build_plan(Settings {
+      directories: vec!["tests/example-dotfiles/folder".to_string()],
+      destination: "tests/non-existing-destination".to_string(),
+      ..Default::default()
+    });
+
+    assert_eq!(plan.operation, Operation::Up);
+    assert_eq!(
+      plan
+        .entries
+        .iter()
+        .map(|e| (e.file_type, e.action, e.reason))
+        .collect::<Vec<_>>(),
+      vec![
+        ("dir", Action::Mkdir, "folder is missing"),
+        ("file", Action::Link, "not installed"),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_plan_conflict() {
+    let plan = build_plan(Settings {
+      directories: vec!["tests/example-dotfiles/tag-um".to_string()],
+      destination: "tests/example-dotfiles/tag-um".to_string(),
+      no_hide: vec!["tag-um".to_string()],
+      copy_includes: vec!["file1.txt$".to_string()],
+      ..Default::default()
+    });
+
+    assert_eq!(
+      plan
+        .entries
+        .iter()
+        .map(|e| (e.method, e.action))
+        .collect::<Vec<_>>(),
+      vec![
+        (Method::Link, Action::Keep),
+        (Method::Link, Action::Keep),
+        (Method::Copy, Action::Keep),
+      ]
+    );
+
+    let plan = build_plan(Settings {
+      directories: vec!["tests/example-dotfiles/tag-um".to_string()],
+      destination: "tests/example-dotfiles/tag-dois".to_string(),
+      no_hide: vec!["tag-um".to_string()],
+      ..Default::default()
+    });
+
+    assert_eq!(
+      plan.entries.iter().map(|e| e.action).collect::<Vec<_>>(),
+      vec![Action::Keep, Action::Conflict, Action::Link]
+    );
+    assert_eq!(
+      plan.conflicts(),
+      vec![PathBuf::from("tests/example-dotfiles/tag-dois/file.txt")]
+    );
+
+    for (conflict, action) in [
+      (ConflictPolicy::Skip, Action::Skip),
+      (ConflictPolicy::Backup, Action::Backup),
+      (ConflictPolicy::Overwrite, Action::Overwrite),
+      (ConflictPolicy::Fail, Action::Conflict),
+    ] {
+      let plan = build_plan(Settings {
+        directories: vec!["tests/example-dotfiles/tag-um".to_string()],
+        destination: "tests/example-dotfiles/tag-dois".to_string(),
+        no_hide: vec!["tag-um".to_string()],
+        conflict,
+        ..Default::default()
+      });
+      assert_eq!(plan.entries[1].action, action);
+    }
+  }
+
+  #[test]
+  fn test_plan_down() {
+    let plan = build_plan(Settings {
+      directories: vec!["tests/example-dotfiles/tag-um".to_string()],
+      destination: "tests/example-dotfiles/tag-dois".to_string(),
+      no_hide: vec!["tag-um".to_string()],
+      operation: Operation::Down,
+      force: true,
+      ..Default::default()
+    });
+
+    assert_eq!(plan.operation, Operation::Down);
+    assert_eq!(
+      plan.entries.iter().map(|e| e.action).collect::<Vec<_>>(),
+      vec![Action::Keep, Action::Delete, Action::Keep]
+    );
+  }
+
+  #[test]
+  fn test_plan_render() {
+    let plan = Plan {
+      operation: Operation::Up,
+      entries: vec![PlanEntry {
+        source: PathBuf::from("dotfiles/file.txt"),
+        destination: PathBuf::from("home/.file.txt"),
+        file_type: "file",
+        method: Method::Link,
+        action: Action::Link,
+        reason: "not installed",
+      }],
+    };
+
+    assert_eq!(
+      plan.render("text").unwrap(),
+      "link \"dotfiles/file.txt\" -> \"home/.file.txt\" (not installed)"
+    );
+    assert_eq!(
+      plan.render("json").unwrap(),
+      r#"{
+  "operation": "up",
+  "entries": [
+    {
+      "source": "dotfiles/file.txt",
+      "destination": "home/.file.txt",
+      "file_type": "file",
+      "method": "link",
+      "action": "link",
+      "reason": "not installed"
+    }
+  ]
+}"#
+    );
+  }
+}
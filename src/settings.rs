@@ -1,19 +1,130 @@
-use crate::nix_helper::{get_hostname, get_user_home};
-use serde::Deserialize;
+use crate::nix_helper::{get_hostname, get_state_dir, get_user_home};
+use crate::patterns::REGEX_PREFIX;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+  Up,
+  Down,
+  Status,
+  Ls,
+  Add,
+  Sync,
+  Restore,
+  Undo,
+}
+
+impl fmt::Display for Operation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Operation::Up => write!(f, "up"),
+      Operation::Down => write!(f, "down"),
+      Operation::Status => write!(f, "status"),
+      Operation::Ls => write!(f, "ls"),
+      Operation::Add => write!(f, "add"),
+      Operation::Sync => write!(f, "sync"),
+      Operation::Restore => write!(f, "restore"),
+      Operation::Undo => write!(f, "undo"),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+  #[default]
+  Prompt,
+  Skip,
+  Overwrite,
+  Backup,
+  Fail,
+}
+
+impl fmt::Display for ConflictPolicy {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ConflictPolicy::Prompt => write!(f, "prompt"),
+      ConflictPolicy::Skip => write!(f, "skip"),
+      ConflictPolicy::Overwrite => write!(f, "overwrite"),
+      ConflictPolicy::Backup => write!(f, "backup"),
+      ConflictPolicy::Fail => write!(f, "fail"),
+    }
+  }
+}
+
+impl FromStr for ConflictPolicy {
+  type Err = String;
+
+  fn from_str(policy: &str) -> Result<Self, Self::Err> {
+    match policy {
+      "prompt" => Ok(ConflictPolicy::Prompt),
+      "skip" => Ok(ConflictPolicy::Skip),
+      "overwrite" => Ok(ConflictPolicy::Overwrite),
+      "backup" => Ok(ConflictPolicy::Backup),
+      "fail" => Ok(ConflictPolicy::Fail),
+      _ => Err(format!("Invalid conflict policy {:?}", policy)),
+    }
+  }
+}
+
+// the config file keeps the `down` key from before the subcommands
+fn operation_from_down<'de, D>(deserializer: D) -> Result<Operation, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  Ok(if bool::deserialize(deserializer)? {
+    Operation::Down
+  } else {
+    Operation::Up
+  })
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Settings {
   pub tags: Vec<String>,
   pub excludes: Vec<String>,
   pub includes: Vec<String>,
+  pub copy_includes: Vec<String>,
+  pub no_hide: Vec<String>,
+  pub symlink_dirs: Vec<String>,
   pub directories: Vec<String>,
   pub destination: String,
   pub hostname: String,
+  pub state_dir: String,
   pub force: bool,
-  pub down: bool,
+  pub conflict: ConflictPolicy,
+  pub no_backup: bool,
+  pub copy: bool,
+  #[serde(rename = "down", deserialize_with = "operation_from_down")]
+  pub operation: Operation,
+  pub sync: bool,
+  pub from_state: bool,
+  pub skip_hooks: bool,
   pub dry_run: bool,
   pub verbose: u8,
+  // a plan only makes sense for one run, not for every run from the config
+  #[serde(skip)]
+  pub plan: bool,
+  #[serde(skip)]
+  pub format: String,
+  #[serde(skip)]
+  pub add: Vec<String>,
+  #[serde(skip)]
+  pub host: bool,
+  #[serde(skip)]
+  pub classify: bool,
+  #[serde(skip)]
+  pub restore: String,
+  // the command line always wins over the config, even for the defaults
+  #[serde(skip)]
+  pub operation_set: bool,
+  #[serde(skip)]
+  pub conflict_set: bool,
 }
 
 impl Settings {
@@ -34,6 +145,21 @@ impl Settings {
       } else {
         self.includes
       },
+      copy_includes: if self.copy_includes.is_empty() {
+        Vec::<String>::new()
+      } else {
+        self.copy_includes
+      },
+      no_hide: if self.no_hide.is_empty() {
+        Vec::<String>::new()
+      } else {
+        self.no_hide
+      },
+      symlink_dirs: if self.symlink_dirs.is_empty() {
+        Vec::<String>::new()
+      } else {
+        self.symlink_dirs
+      },
       directories: if self.directories.is_empty() {
         vec![get_user_home() + "/.dotfiles"]
       } else {
@@ -49,34 +175,61 @@ impl Settings {
       } else {
         self.hostname
       },
+      state_dir: if self.state_dir.is_empty() {
+        get_state_dir()
+      } else {
+        self.state_dir
+      },
       force: self.force,
-      down: self.down,
+      conflict: self.conflict,
+      no_backup: self.no_backup,
+      copy: self.copy,
+      operation: self.operation,
+      sync: self.sync,
+      from_state: self.from_state,
+      skip_hooks: self.skip_hooks,
       dry_run: self.dry_run,
+      plan: self.plan,
+      format: if self.format.is_empty() {
+        "text".to_string()
+      } else {
+        self.format
+      },
       verbose: self.verbose,
+      add: self.add,
+      host: self.host,
+      classify: self.classify,
+      restore: self.restore,
+      operation_set: self.operation_set,
+      conflict_set: self.conflict_set,
     }
   }
 
   pub fn merge(self, other: Settings) -> Self {
     Self {
-      tags: self
-        .tags
-        .into_iter()
-        .chain(other.tags.into_iter())
-        .collect(),
-      excludes: self
-        .excludes
+      // only the -t of this run picks the folder paro add moves files into
+      tags: if other.operation == Operation::Add {
+        other.tags
+      } else {
+        self.tags.into_iter().chain(other.tags).collect()
+      },
+      excludes: self.excludes.into_iter().chain(other.excludes).collect(),
+      includes: self.includes.into_iter().chain(other.includes).collect(),
+      copy_includes: self
+        .copy_includes
         .into_iter()
-        .chain(other.excludes.into_iter())
+        .chain(other.copy_includes)
         .collect(),
-      includes: self
-        .includes
+      no_hide: self.no_hide.into_iter().chain(other.no_hide).collect(),
+      symlink_dirs: self
+        .symlink_dirs
         .into_iter()
-        .chain(other.includes.into_iter())
+        .chain(other.symlink_dirs)
         .collect(),
       directories: self
         .directories
         .into_iter()
-        .chain(other.directories.into_iter())
+        .chain(other.directories)
         .collect(),
       destination: if other.destination.is_empty() {
         self.destination
@@ -88,10 +241,61 @@ impl Settings {
       } else {
         other.hostname
       },
+      state_dir: if other.state_dir.is_empty() {
+        self.state_dir
+      } else {
+        other.state_dir
+      },
       force: self.force || other.force,
-      down: self.down || other.down,
+      conflict: if other.conflict_set {
+        other.conflict
+      } else {
+        self.conflict
+      },
+      no_backup: self.no_backup || other.no_backup,
+      copy: self.copy || other.copy,
+      operation: if other.operation_set {
+        other.operation
+      } else {
+        self.operation
+      },
+      sync: self.sync || other.sync,
+      from_state: self.from_state || other.from_state,
+      skip_hooks: self.skip_hooks || other.skip_hooks,
       dry_run: self.dry_run || other.dry_run,
+      plan: self.plan || other.plan,
+      format: if other.format.is_empty() {
+        self.format
+      } else {
+        other.format
+      },
       verbose: self.verbose | other.verbose,
+      add: self.add.into_iter().chain(other.add).collect(),
+      host: self.host || other.host,
+      classify: self.classify || other.classify,
+      restore: if other.restore.is_empty() {
+        self.restore
+      } else {
+        other.restore
+      },
+      operation_set: self.operation_set || other.operation_set,
+      conflict_set: self.conflict_set || other.conflict_set,
+    }
+  }
+
+  pub fn conflict_policy(&self) -> ConflictPolicy {
+    if self.force {
+      ConflictPolicy::Overwrite
+    } else {
+      self.conflict
+    }
+  }
+
+  pub fn removes_stale_links(&self) -> bool {
+    match self.operation {
+      Operation::Sync => true,
+      Operation::Up => self.sync,
+      _ => false,
     }
   }
 
@@ -109,6 +313,47 @@ impl Settings {
 
     special_folders
   }
+
+  pub fn excludes_for(&self, dir: &str) -> Vec<String> {
+    scoped_patterns(&self.excludes, dir)
+  }
+
+  pub fn includes_for(&self, dir: &str) -> Vec<String> {
+    scoped_patterns(&self.includes, dir)
+  }
+}
+
+// like in rcm, dir:pattern only applies to the dotfiles folder named dir and
+// *:pattern or a plain pattern to all of them
+pub fn split_scope(pattern: &str) -> (Option<&str>, &str) {
+  if pattern.starts_with(REGEX_PREFIX) {
+    return (None, pattern);
+  }
+  match pattern.split_once(':') {
+    Some(("*", pattern)) => (None, pattern),
+    Some((scope, pattern)) => (Some(scope), pattern),
+    None => (None, pattern),
+  }
+}
+
+// the folder is named by its path as configured or by its last component
+fn is_scope_of(scope: &str, dir: &str) -> bool {
+  let dir = Path::new(dir);
+  dir == Path::new(scope) || dir.file_name() == Some(scope.as_ref())
+}
+
+// a scope naming a folder this machine does not have applies nowhere, like
+// in rcm, so a shared config can scope patterns to any checkout
+fn scoped_patterns(patterns: &[String], dir: &str) -> Vec<String> {
+  patterns
+    .iter()
+    .filter_map(|pattern| match split_scope(pattern) {
+      (Some(scope), pattern) if is_scope_of(scope, dir) => Some(pattern),
+      (Some(_), _) => None,
+      (None, pattern) => Some(pattern),
+    })
+    .map(|pattern| pattern.to_string())
+    .collect()
 }
 
 impl Default for Settings {
@@ -117,13 +362,31 @@ impl Default for Settings {
       tags: Vec::<String>::new(),
       excludes: Vec::<String>::new(),
       includes: Vec::<String>::new(),
+      copy_includes: Vec::<String>::new(),
+      no_hide: Vec::<String>::new(),
+      symlink_dirs: Vec::<String>::new(),
       directories: Vec::<String>::new(),
       destination: "".to_string(),
       hostname: "".to_string(),
+      state_dir: "".to_string(),
       force: false,
-      down: false,
+      conflict: ConflictPolicy::Prompt,
+      no_backup: false,
+      copy: false,
+      operation: Operation::Up,
+      sync: false,
+      from_state: false,
+      skip_hooks: false,
       dry_run: false,
+      plan: false,
+      format: "".to_string(),
       verbose: 0,
+      add: Vec::<String>::new(),
+      host: false,
+      classify: false,
+      restore: "".to_string(),
+      operation_set: false,
+      conflict_set: false,
     }
   }
 }
@@ -142,26 +405,68 @@ mod tests {
       tags: to_string_vec(vec!["t1", "t1"]),
       excludes: to_string_vec(vec!["e1", "e1"]),
       includes: to_string_vec(vec!["i1", "i1"]),
+      copy_includes: to_string_vec(vec!["c1", "c1"]),
+      no_hide: to_string_vec(vec!["n1", "n1"]),
+      symlink_dirs: to_string_vec(vec!["s1", "s1"]),
       directories: to_string_vec(vec!["d1", "d1"]),
       destination: "dn1".to_string(),
       hostname: "h1".to_string(),
+      state_dir: "sd1".to_string(),
       force: true,
-      down: true,
+      conflict: ConflictPolicy::Skip,
+      no_backup: true,
+      copy: true,
+      operation: Operation::Down,
+      sync: true,
+      from_state: true,
+      skip_hooks: true,
       dry_run: true,
+      plan: true,
+      format: "json".to_string(),
       verbose: 2,
+      add: to_string_vec(vec!["a1", "a1"]),
+      host: true,
+      classify: true,
+      restore: "r1".to_string(),
+      operation_set: true,
+      conflict_set: true,
     };
 
     let settings_with_defaults = settings_1.clone().with_defaults();
     assert_eq!(settings_with_defaults.tags, settings_1.tags);
     assert_eq!(settings_with_defaults.excludes, settings_1.excludes);
     assert_eq!(settings_with_defaults.includes, settings_1.includes);
+    assert_eq!(
+      settings_with_defaults.copy_includes,
+      settings_1.copy_includes
+    );
+    assert_eq!(settings_with_defaults.no_hide, settings_1.no_hide);
+    assert_eq!(settings_with_defaults.symlink_dirs, settings_1.symlink_dirs);
     assert_eq!(settings_with_defaults.directories, settings_1.directories);
     assert_eq!(settings_with_defaults.destination, settings_1.destination);
     assert_eq!(settings_with_defaults.hostname, settings_1.hostname);
+    assert_eq!(settings_with_defaults.state_dir, settings_1.state_dir);
     assert_eq!(settings_with_defaults.force, settings_1.force);
-    assert_eq!(settings_with_defaults.down, settings_1.down);
+    assert_eq!(settings_with_defaults.conflict, settings_1.conflict);
+    assert_eq!(settings_with_defaults.no_backup, settings_1.no_backup);
+    assert_eq!(settings_with_defaults.copy, settings_1.copy);
+    assert_eq!(settings_with_defaults.operation, settings_1.operation);
+    assert_eq!(settings_with_defaults.sync, settings_1.sync);
+    assert_eq!(settings_with_defaults.from_state, settings_1.from_state);
+    assert_eq!(settings_with_defaults.skip_hooks, settings_1.skip_hooks);
     assert_eq!(settings_with_defaults.dry_run, settings_1.dry_run);
+    assert_eq!(settings_with_defaults.plan, settings_1.plan);
+    assert_eq!(settings_with_defaults.format, settings_1.format);
     assert_eq!(settings_with_defaults.verbose, settings_1.verbose);
+    assert_eq!(settings_with_defaults.add, settings_1.add);
+    assert_eq!(settings_with_defaults.host, settings_1.host);
+    assert_eq!(settings_with_defaults.classify, settings_1.classify);
+    assert_eq!(settings_with_defaults.restore, settings_1.restore);
+    assert_eq!(
+      settings_with_defaults.operation_set,
+      settings_1.operation_set
+    );
+    assert_eq!(settings_with_defaults.conflict_set, settings_1.conflict_set);
   }
 
   #[test]
@@ -171,38 +476,92 @@ mod tests {
       tags: to_string_vec(vec!["t1", "t1"]),
       excludes: to_string_vec(vec!["e1", "e1"]),
       includes: to_string_vec(vec!["i1", "i1"]),
+      copy_includes: to_string_vec(vec!["c1", "c1"]),
+      no_hide: to_string_vec(vec!["n1", "n1"]),
+      symlink_dirs: to_string_vec(vec!["s1", "s1"]),
       directories: to_string_vec(vec!["d1", "d1"]),
       destination: "dn1".to_string(),
       hostname: "h1".to_string(),
+      state_dir: "sd1".to_string(),
       force: true,
-      down: true,
+      conflict: ConflictPolicy::Skip,
+      no_backup: true,
+      copy: true,
+      operation: Operation::Down,
+      sync: true,
+      from_state: true,
+      skip_hooks: true,
       dry_run: true,
+      plan: true,
+      format: "json".to_string(),
       verbose: 2,
+      add: to_string_vec(vec!["a1", "a1"]),
+      host: true,
+      classify: true,
+      restore: "r1".to_string(),
+      operation_set: true,
+      conflict_set: true,
     };
     let settings_2 = Settings {
       tags: to_string_vec(vec!["t2", "t2"]),
       excludes: to_string_vec(vec!["e2", "e2"]),
       includes: to_string_vec(vec!["i2", "i2"]),
+      copy_includes: to_string_vec(vec!["c2", "c2"]),
+      no_hide: to_string_vec(vec!["n2", "n2"]),
+      symlink_dirs: to_string_vec(vec!["s2", "s2"]),
       directories: to_string_vec(vec!["d2", "d2"]),
       destination: "dn2".to_string(),
       hostname: "h2".to_string(),
+      state_dir: "sd2".to_string(),
       force: false,
-      down: false,
+      conflict: ConflictPolicy::Overwrite,
+      no_backup: false,
+      copy: false,
+      operation: Operation::Up,
+      sync: false,
+      from_state: false,
+      skip_hooks: false,
       dry_run: false,
+      plan: false,
+      format: "text".to_string(),
       verbose: 0,
+      add: to_string_vec(vec!["a2", "a2"]),
+      host: false,
+      classify: false,
+      restore: "r2".to_string(),
+      operation_set: false,
+      conflict_set: true,
     };
 
     let merged_settings = settings_empty.merge(settings_1.clone());
     assert_eq!(merged_settings.tags, settings_1.tags);
     assert_eq!(merged_settings.excludes, settings_1.excludes);
     assert_eq!(merged_settings.includes, settings_1.includes);
+    assert_eq!(merged_settings.copy_includes, settings_1.copy_includes);
+    assert_eq!(merged_settings.no_hide, settings_1.no_hide);
+    assert_eq!(merged_settings.symlink_dirs, settings_1.symlink_dirs);
     assert_eq!(merged_settings.directories, settings_1.directories);
     assert_eq!(merged_settings.destination, settings_1.destination);
     assert_eq!(merged_settings.hostname, settings_1.hostname);
+    assert_eq!(merged_settings.state_dir, settings_1.state_dir);
     assert_eq!(merged_settings.force, settings_1.force);
-    assert_eq!(merged_settings.down, settings_1.down);
+    assert_eq!(merged_settings.conflict, settings_1.conflict);
+    assert_eq!(merged_settings.no_backup, settings_1.no_backup);
+    assert_eq!(merged_settings.copy, settings_1.copy);
+    assert_eq!(merged_settings.operation, settings_1.operation);
+    assert_eq!(merged_settings.sync, settings_1.sync);
+    assert_eq!(merged_settings.from_state, settings_1.from_state);
+    assert_eq!(merged_settings.skip_hooks, settings_1.skip_hooks);
     assert_eq!(merged_settings.dry_run, settings_1.dry_run);
+    assert_eq!(merged_settings.plan, settings_1.plan);
+    assert_eq!(merged_settings.format, settings_1.format);
     assert_eq!(merged_settings.verbose, settings_1.verbose);
+    assert_eq!(merged_settings.add, settings_1.add);
+    assert_eq!(merged_settings.host, settings_1.host);
+    assert_eq!(merged_settings.classify, settings_1.classify);
+    assert_eq!(merged_settings.restore, settings_1.restore);
+    assert!(merged_settings.operation_set);
+    assert!(merged_settings.conflict_set);
 
     let merged2_settings = settings_1.clone().merge(settings_2.clone());
     assert_eq!(
@@ -217,15 +576,162 @@ mod tests {
       merged2_settings.includes,
       to_string_vec(vec!["i1", "i1", "i2", "i2"])
     );
+    assert_eq!(
+      merged2_settings.copy_includes,
+      to_string_vec(vec!["c1", "c1", "c2", "c2"])
+    );
+    assert_eq!(
+      merged2_settings.no_hide,
+      to_string_vec(vec!["n1", "n1", "n2", "n2"])
+    );
+    assert_eq!(
+      merged2_settings.symlink_dirs,
+      to_string_vec(vec!["s1", "s1", "s2", "s2"])
+    );
     assert_eq!(
       merged2_settings.directories,
       to_string_vec(vec!["d1", "d1", "d2", "d2"])
     );
     assert_eq!(merged2_settings.destination, settings_2.destination);
     assert_eq!(merged2_settings.hostname, settings_2.hostname);
+    assert_eq!(merged2_settings.state_dir, settings_2.state_dir);
     assert_eq!(merged2_settings.force, settings_1.force);
-    assert_eq!(merged2_settings.down, settings_1.down);
+    assert_eq!(merged2_settings.conflict, settings_2.conflict);
+    assert_eq!(merged2_settings.no_backup, settings_1.no_backup);
+    assert_eq!(merged2_settings.copy, settings_1.copy);
+    assert_eq!(merged2_settings.operation, settings_1.operation);
+    assert_eq!(merged2_settings.sync, settings_1.sync);
+    assert_eq!(merged2_settings.from_state, settings_1.from_state);
+    assert_eq!(merged2_settings.skip_hooks, settings_1.skip_hooks);
     assert_eq!(merged2_settings.dry_run, settings_1.dry_run);
+    assert_eq!(merged2_settings.plan, settings_1.plan);
+    assert_eq!(merged2_settings.format, settings_2.format);
     assert_eq!(merged2_settings.verbose, settings_1.verbose);
+    assert_eq!(
+      merged2_settings.add,
+      to_string_vec(vec!["a1", "a1", "a2", "a2"])
+    );
+    assert_eq!(merged2_settings.host, settings_1.host);
+    assert_eq!(merged2_settings.classify, settings_1.classify);
+    assert_eq!(merged2_settings.restore, settings_2.restore);
+    assert!(merged2_settings.operation_set);
+    assert!(merged2_settings.conflict_set);
+  }
+
+  #[test]
+  fn test_merge_command_line_wins() {
+    let config = Settings {
+      operation: Operation::Down,
+      conflict: ConflictPolicy::Skip,
+      ..Default::default()
+    };
+
+    // paro up --conflict prompt, the same values as the defaults
+    let merged = config.clone().merge(Settings {
+      operation_set: true,
+      conflict_set: true,
+      ..Default::default()
+    });
+    assert_eq!(merged.operation, Operation::Up);
+    assert_eq!(merged.conflict, ConflictPolicy::Prompt);
+
+    // plain paro keeps what the config says
+    let merged = config.merge(Settings::default());
+    assert_eq!(merged.operation, Operation::Down);
+    assert_eq!(merged.conflict, ConflictPolicy::Skip);
+  }
+
+  #[test]
+  fn test_merge_add_tags() {
+    let config = Settings {
+      tags: vec!["linux".to_string()],
+      ..Default::default()
+    };
+    let add = |tags: Vec<String>| Settings {
+      tags,
+      operation: Operation::Add,
+      operation_set: true,
+      ..Default::default()
+    };
+
+    assert!(config.clone().merge(add(vec![])).tags.is_empty());
+    assert_eq!(
+      config.clone().merge(add(vec!["work".to_string()])).tags,
+      ["work"]
+    );
+    assert_eq!(config.merge(Settings::default()).tags, ["linux"]);
+  }
+
+  #[test]
+  fn test_removes_stale_links() {
+    let settings = |operation: Operation, sync: bool| Settings {
+      operation,
+      sync,
+      ..Default::default()
+    };
+    assert!(settings(Operation::Sync, false).removes_stale_links());
+    assert!(settings(Operation::Up, true).removes_stale_links());
+    assert!(!settings(Operation::Up, false).removes_stale_links());
+    assert!(!settings(Operation::Down, true).removes_stale_links());
+  }
+
+  #[test]
+  fn test_scoped_patterns() {
+    let settings = Settings {
+      excludes: vec![
+        "*.md".to_string(),
+        "work-dotfiles:README".to_string(),
+        "tests/example-dotfiles/:bin".to_string(),
+        "*:a:b".to_string(),
+        "re:^tag-".to_string(),
+      ],
+      includes: vec!["work-dotfiles:.ignored-file".to_string()],
+      directories: vec![
+        "/home/user/work-dotfiles".to_string(),
+        "tests/example-dotfiles".to_string(),
+        "/home/user/.dotfiles".to_string(),
+      ],
+      ..Default::default()
+    };
+
+    assert_eq!(
+      settings.excludes_for("/home/user/work-dotfiles"),
+      ["*.md", "README", "a:b", "re:^tag-"]
+    );
+    assert_eq!(
+      settings.excludes_for("tests/example-dotfiles"),
+      ["*.md", "bin", "a:b", "re:^tag-"]
+    );
+    assert_eq!(
+      settings.includes_for("/home/user/work-dotfiles"),
+      [".ignored-file"]
+    );
+    assert!(settings.includes_for("/home/user/.dotfiles").is_empty());
+    assert_eq!(split_scope("dir:re:^a"), (Some("dir"), "re:^a"));
+
+    // a folder this machine does not have is skipped, not an error
+    let settings = Settings {
+      excludes: vec!["other-dotfiles:*.md".to_string()],
+      ..settings
+    };
+    assert!(settings.excludes_for("tests/example-dotfiles").is_empty());
+  }
+
+  #[test]
+  fn test_conflict_policy() {
+    let settings = Settings {
+      conflict: ConflictPolicy::Backup,
+      ..Default::default()
+    };
+    assert_eq!(settings.conflict_policy(), ConflictPolicy::Backup);
+    assert_eq!("fail".parse(), Ok(ConflictPolicy::Fail));
+    assert!("other".parse::<ConflictPolicy>().is_err());
+
+    // force is the same as overwrite
+    let settings = Settings {
+      force: true,
+      ..settings
+    };
+    assert_eq!(settings.conflict_policy(), ConflictPolicy::Overwrite);
   }
 }
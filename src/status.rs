@@ -0,0 +1,234 @@
+use crate::error::ParoError;
+use crate::file_actions::{FileActions, FileEntry, Method};
+use crate::files;
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+  Linked,
+  Missing,
+  Conflict,
+  Elsewhere,
+  Dangling,
+}
+
+impl fmt::Display for State {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      State::Linked => write!(f, "linked"),
+      State::Missing => write!(f, "missing"),
+      State::Conflict => write!(f, "conflict"),
+      State::Elsewhere => write!(f, "elsewhere"),
+      State::Dangling => write!(f, "dangling"),
+    }
+  }
+}
+
+pub fn classify(value: &FileEntry, key: &Path) -> io::Result<State> {
+  let copy = value.method == Method::Copy;
+  if files::is_same_file(&value.path, key, copy)? {
+    return Ok(State::Linked);
+  }
+
+  if !files::is_present(key) {
+    return Ok(State::Missing);
+  }
+
+  if key.is_symlink() {
+    return Ok(if key.exists() {
+      State::Elsewhere
+    } else {
+      State::Dangling
+    });
+  }
+
+  Ok(State::Conflict)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StatusEntry {
+  pub source: PathBuf,
+  pub destination: PathBuf,
+  pub method: Method,
+  pub state: State,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+  pub linked: usize,
+  pub missing: usize,
+  pub conflict: usize,
+  pub elsewhere: usize,
+  pub dangling: usize,
+}
+
+impl Summary {
+  fn add(&mut self, state: State) {
+    match state {
+      State::Linked => self.linked += 1,
+      State::Missing => self.missing += 1,
+      State::Conflict => self.conflict += 1,
+      State::Elsewhere => self.elsewhere += 1,
+      State::Dangling => self.dangling += 1,
+    }
+  }
+
+  pub fn drift(&self) -> usize {
+    self.missing + self.conflict + self.elsewhere + self.dangling
+  }
+}
+
+impl fmt::Display for Summary {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} linked, {} missing, {} conflict, {} elsewhere, {} dangling",
+      self.linked, self.missing, self.conflict, self.elsewhere, self.dangling
+    )
+  }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Status {
+  pub entries: Vec<StatusEntry>,
+  pub summary: Summary,
+}
+
+impl Status {
+  pub fn new(file_actions: &FileActions) -> Result<Self, ParoError> {
+    let mut entries = Vec::new();
+    let mut summary = Summary::default();
+
+    for (key, value) in &file_actions.actions {
+      // folders are only created by paro, their files tell the status
+      if value.path.is_dir() && !value.symlink_dir {
+        continue;
+      }
+
+      let state =
+        classify(value, key).map_err(|err| ParoError::io(err, key))?;
+      summary.add(state);
+      entries.push(StatusEntry {
+        source: value.path.clone(),
+        destination: key.clone(),
+        method: value.method,
+        state,
+      });
+    }
+
+    Ok(Self { entries, summary })
+  }
+
+  pub fn render(&self, format: &str, verbose: u8) -> Result<String, ParoError> {
+    if format == "json" {
+      return serde_json::to_string_pretty(self)
+        .map_err(|err| ParoError::Config(err.to_string()));
+    }
+
+    let mut lines: Vec<String> = self
+      .entries
+      .iter()
+      .filter(|e| verbose > 0 || e.state != State::Linked)
+      .map(|e| format!("{} {:?} -> {:?}", e.state, e.destination, e.source))
+      .collect();
+    lines.push(self.summary.to_string());
+    Ok(lines.join("\n"))
+  }
+
+  pub fn check(&self) -> Result<(), ParoError> {
+    match self.summary.drift() {
+      0 => Ok(()),
+      drift => Err(ParoError::Drift(drift)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::settings::Settings;
+  use std::fs;
+  use std::os::unix::fs::symlink;
+
+  fn entry(path: &str) -> FileEntry {
+    FileEntry {
+      path: PathBuf::from(path),
+      file_type: fs::metadata(path).unwrap().file_type(),
+      depth: 1,
+      method: Method::Link,
+      symlink_dir: false,
+      special_folder: None,
+    }
+  }
+
+  #[test]
+  fn test_classify() {
+    let dir = Path::new("tests/status-destination");
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let source = "tests/example-dotfiles/tag-um/file.txt";
+    let value = entry(source);
+
+    symlink(fs::canonicalize(source).unwrap(), dir.join("linked")).unwrap();
+    fs::write(dir.join("conflict"), "real file").unwrap();
+    symlink(
+      fs::canonicalize("tests/example-dotfiles/tag-dois/file2.txt").unwrap(),
+      dir.join("elsewhere"),
+    )
+    .unwrap();
+    symlink(dir.join("non-existing"), dir.join("dangling")).unwrap();
+
+    let states: Vec<State> =
+      ["linked", "missing", "conflict", "elsewhere", "dangling"]
+        .iter()
+        .map(|name| classify(&value, &dir.join(name)).unwrap())
+        .collect();
+    fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(
+      states,
+      vec![
+        State::Linked,
+        State::Missing,
+        State::Conflict,
+        State::Elsewhere,
+        State::Dangling,
+      ]
+    );
+  }
+
+  #[test]
+  fn test_status() {
+    let file_actions = FileActions::new(Settings {
+      directories: vec!["tests/example-dotfiles/tag-um".to_string()],
+      destination: "tests/example-dotfiles/tag-dois".to_string(),
+      no_hide: vec!["tag-um".to_string()],
+      ..Default::default()
+    })
+    .build()
+    .unwrap();
+    let status = Status::new(&file_actions).unwrap();
+
+    assert_eq!(
+      status.summary,
+      Summary {
+        conflict: 1,
+        missing: 1,
+        ..Default::default()
+      }
+    );
+    assert_eq!(status.check().unwrap_err().exit_code(), 5);
+    assert_eq!(
+      status.render("text", 0).unwrap(),
+      "conflict \"tests/example-dotfiles/tag-dois/file.txt\" -> \
+       \"tests/example-dotfiles/tag-um/file.txt\"\n\
+       missing \"tests/example-dotfiles/tag-dois/file1.txt\" -> \
+       \"tests/example-dotfiles/tag-um/file1.txt\"\n\
+       0 linked, 1 missing, 1 conflict, 0 elsewhere, 0 dangling"
+    );
+  }
+}
@@ -1,26 +1,71 @@
 use std::fmt;
-use std::io::{stdout, Stdout, Write};
+use std::io::{self, stdout, Stdout, Write};
+use termion::event::Key;
 use termion::input::{Keys, TermRead};
 use termion::raw::{IntoRawMode, RawTerminal};
 use termion::{async_stdin, AsyncReader};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Inputs {
   Yes,
   No,
+  YesAll,
+  NoAll,
+  Diff,
+  Backup,
   Exit,
 }
 
+impl Inputs {
+  pub fn key(&self) -> char {
+    match self {
+      Inputs::Yes => 'y',
+      Inputs::No => 'n',
+      Inputs::YesAll => 'a',
+      Inputs::NoAll => 's',
+      Inputs::Diff => 'd',
+      Inputs::Backup => 'b',
+      Inputs::Exit => 'q',
+    }
+  }
+
+  pub fn help(&self) -> &'static str {
+    match self {
+      Inputs::Yes => "yes",
+      Inputs::No => "no, the default",
+      Inputs::YesAll => "yes to this and all the remaining files",
+      Inputs::NoAll => "no to this and all the remaining files",
+      Inputs::Diff => "show the differences to the dotfile",
+      Inputs::Backup => "backup the current file and replace it",
+      Inputs::Exit => "quit",
+    }
+  }
+}
+
 impl fmt::Display for Inputs {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
       Inputs::Yes => write!(f, "Yes"),
       Inputs::No => write!(f, "No"),
+      Inputs::YesAll => write!(f, "Yes to all"),
+      Inputs::NoAll => write!(f, "No to all"),
+      Inputs::Diff => write!(f, "Diff"),
+      Inputs::Backup => write!(f, "Backup"),
       Inputs::Exit => write!(f, "Exit"),
     }
   }
 }
 
+pub fn choices_hint(choices: &[Inputs]) -> String {
+  let keys: Vec<String> = choices.iter().map(|c| c.key().to_string()).collect();
+  format!("[{},?]", keys.join(","))
+}
+
+pub trait Console {
+  fn writeln(&mut self, message: String);
+  fn dialog(&mut self, question: String, choices: &[Inputs]) -> Inputs;
+}
+
 pub struct Stdio {
   pub stdout: RawTerminal<Stdout>,
   pub stdin: Keys<AsyncReader>,
@@ -34,47 +79,98 @@ impl Stdio {
     }
   }
 
-  pub fn writeln(&mut self, message: String) {
-    write!(self.stdout, "{}\r\n", message).unwrap();
-  }
-
   pub fn write(&mut self, message: String) {
     write!(self.stdout, "{}", message).unwrap();
   }
 
-  pub fn read_input(&mut self) -> Inputs {
-    let mut input = Inputs::No;
+  // None when the user asks for help
+  pub fn read_input(&mut self, choices: &[Inputs]) -> Option<Inputs> {
     loop {
       if let Some(Ok(key)) = self.stdin.next() {
         match key {
-          termion::event::Key::Char('y') => {
-            input = Inputs::Yes;
-            break;
-          }
-          termion::event::Key::Char('n') => {
-            input = Inputs::No;
-            break;
+          Key::Esc | Key::Char('\n') => return Some(Inputs::No),
+          Key::Ctrl('d') | Key::Ctrl('c') => return Some(Inputs::Exit),
+          Key::Char('?') => return None,
+          Key::Char(c) => {
+            if let Some(input) = choices.iter().find(|i| i.key() == c) {
+              return Some(*input);
+            }
           }
-          termion::event::Key::Esc | termion::event::Key::Char('\n') => break,
-          termion::event::Key::Ctrl('d') | termion::event::Key::Ctrl('c') => {
-            input = Inputs::Exit;
-            break;
-          }
-          _ => {
-            self.stdout.lock().flush().unwrap();
-            continue;
+          _ => {}
+        }
+        self.stdout.lock().flush().unwrap();
+      }
+    }
+  }
+}
+
+impl Console for Stdio {
+  fn writeln(&mut self, message: String) {
+    write!(self.stdout, "{}\r\n", message).unwrap();
+  }
+
+  fn dialog(&mut self, question: String, choices: &[Inputs]) -> Inputs {
+    loop {
+      self.write(format!("{} {} ", question, choices_hint(choices)));
+      self.stdout.lock().flush().unwrap();
+      match self.read_input(choices) {
+        Some(input) => {
+          self.writeln(format!("{}", &input));
+          return input;
+        }
+        None => {
+          self.writeln("?".to_string());
+          for choice in choices {
+            self.writeln(format!("{} - {}", choice.key(), choice.help()));
           }
         }
       }
     }
-    input
   }
+}
+
+pub struct Plain;
+
+impl Console for Plain {
+  fn writeln(&mut self, message: String) {
+    println!("{}", message);
+  }
+
+  // nobody can answer without a terminal, keep what is there
+  fn dialog(&mut self, question: String, choices: &[Inputs]) -> Inputs {
+    println!(
+      "{} {} {} (not a terminal)",
+      question,
+      choices_hint(choices),
+      Inputs::No
+    );
+    Inputs::No
+  }
+}
+
+pub fn console() -> Box<dyn Console> {
+  if termion::is_tty(&io::stdin()) && termion::is_tty(&io::stdout()) {
+    Box::new(Stdio::new())
+  } else {
+    Box::new(Plain)
+  }
+}
+
+impl Default for Stdio {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-  pub fn dialog(&mut self, question: String) -> Inputs {
-    self.write(question + " [y/N] ");
-    self.stdout.lock().flush().unwrap();
-    let input = self.read_input();
-    self.writeln(format!("{}", &input));
-    input
+  #[test]
+  fn test_choices_hint() {
+    assert_eq!(
+      choices_hint(&[Inputs::Yes, Inputs::No, Inputs::Diff, Inputs::Exit]),
+      "[y,n,d,q,?]"
+    );
   }
 }